@@ -0,0 +1,456 @@
+use std::io;
+use std::io::prelude::*;
+use std::fs::File;
+use sdl2::render::Renderer;
+use time;
+use lines::LineBatch;
+use math::Vec2d;
+use pitch;
+use puzzle::Sound;
+use options::dim_background;
+use stats::read_save_file;
+use theme::Theme;
+use draw_string;
+
+// Correct picks in a row between streak milestones.
+pub const STREAK_MILESTONE: i32 = 5;
+// Streak length for the streak achievement.
+const STREAK_GOAL: i32 = 10;
+// Level that must be answered correctly for the level achievement.
+const LEVEL_GOAL: u32 = 30;
+// Answers in a day, with no misses, for a perfect daily.
+const DAILY_ROUNDS: u32 = 20;
+// Target frequency for the waveform achievement, and how many cents
+// either side still count.
+const WAVEFORM_FREQ: f32 = 110.0;
+const WAVEFORM_TOLERANCE: f32 = 50.0;
+
+/////////////////////////////////////////////////////////////////////
+// Achievement
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Achievement
+{
+	// First streak of STREAK_GOAL.
+	Streak,
+	// Correct answer at LEVEL_GOAL.
+	ClearLevel,
+	// DAILY_ROUNDS answers in a day without a miss.
+	PerfectDaily,
+	// Each waveform identified as the dominant one of a target at WAVEFORM_FREQ.
+	Waveforms,
+}
+
+pub const ACHIEVEMENTS: [Achievement; 4] = [Achievement::Streak, Achievement::ClearLevel, Achievement::PerfectDaily, Achievement::Waveforms];
+
+impl Achievement
+{
+	// Name used in the save file.
+	pub fn name(&self) -> &'static str
+	{
+		match *self
+		{
+			Achievement::Streak => "streak",
+			Achievement::ClearLevel => "clear_level",
+			Achievement::PerfectDaily => "perfect_daily",
+			Achievement::Waveforms => "waveforms",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Achievement>
+	{
+		ACHIEVEMENTS.iter().find(|achievement| achievement.name() == name).map(|achievement| *achievement)
+	}
+
+	pub fn title(&self) -> String
+	{
+		match *self
+		{
+			Achievement::Streak => format!("STREAK OF {}", STREAK_GOAL),
+			Achievement::ClearLevel => format!("CLEAR LEVEL {}", LEVEL_GOAL),
+			Achievement::PerfectDaily => "PERFECT DAILY".to_string(),
+			Achievement::Waveforms => format!("ALL WAVES AT {} HZ", WAVEFORM_FREQ),
+		}
+	}
+
+	pub fn description(&self) -> String
+	{
+		match *self
+		{
+			Achievement::Streak => format!("{} CORRECT IN A ROW", STREAK_GOAL),
+			Achievement::ClearLevel => format!("ANSWER LEVEL {} CORRECTLY", LEVEL_GOAL),
+			Achievement::PerfectDaily => format!("{} ANSWERS IN A DAY WITH NO MISSES", DAILY_ROUNDS),
+			Achievement::Waveforms => "PICK SINE SQUARE AND SAWTOOTH TARGETS".to_string(),
+		}
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// Streak
+// Correct picks in a row this session.
+pub struct Streak
+{
+	pub current: i32,
+	pub best: i32,
+}
+
+impl Streak
+{
+	pub fn new() -> Streak
+	{
+		Streak
+		{
+			current: 0,
+			best: 0,
+		}
+	}
+
+	// Record an answer, returning the streak if it reached a milestone.
+	pub fn record(&mut self, hit: bool) -> Option<i32>
+	{
+		if !hit
+		{
+			self.current = 0;
+			return None;
+		}
+		self.current += 1;
+		self.best = self.best.max(self.current);
+		if self.current % STREAK_MILESTONE == 0 { Some(self.current) } else { None }
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// Achievements
+// Unlocks and the progress towards them, kept between sessions.
+pub struct Achievements
+{
+	unlocked: [bool; 4],
+	// Day of the daily count as YYYYMMDD, with answers and misses on that day.
+	daily_date: String,
+	daily_answers: u32,
+	daily_misses: u32,
+	// Waveforms identified at WAVEFORM_FREQ so far.
+	waveforms: [bool; 3],
+}
+
+// Today as YYYYMMDD in local time.
+fn today() -> String
+{
+	let now = time::now();
+	match now.strftime("%Y%m%d")
+	{
+		Ok(date) => date.to_string(),
+		Err(..) => format!("{:04}{:02}{:02}", now.tm_year + 1900, now.tm_mon + 1, now.tm_mday),
+	}
+}
+
+impl Achievements
+{
+	pub fn new() -> Achievements
+	{
+		Achievements
+		{
+			unlocked: [false; 4],
+			daily_date: today(),
+			daily_answers: 0,
+			daily_misses: 0,
+			waveforms: [false; 3],
+		}
+	}
+
+	// Load unlocks, starting afresh if the file is missing. Unrecognised lines are skipped.
+	pub fn load(filename: &str) -> Achievements
+	{
+		Achievements::load_on(filename, today())
+	}
+
+	// As load, on date.
+	fn load_on(filename: &str, date: String) -> Achievements
+	{
+		let mut achievements = Achievements::new();
+		achievements.daily_date = date;
+		let s = match read_save_file(filename)
+		{
+			Some(s) => s,
+			None => return achievements,
+		};
+
+		for line in s.lines()
+		{
+			let parts: Vec<&str> = line.split_whitespace().collect();
+			match (parts.first().map(|part| *part), parts.len())
+			{
+				(Some("unlocked"), 2) =>
+				{
+					if let Some(achievement) = Achievement::from_name(parts[1])
+					{
+						achievements.unlocked[achievement as usize] = true;
+					}
+				},
+				// A count from an earlier day is dropped.
+				(Some("daily"), 4) if parts[1] == achievements.daily_date =>
+				{
+					if let (Ok(answers), Ok(misses)) = (parts[2].parse::<u32>(), parts[3].parse::<u32>())
+					{
+						achievements.daily_answers = answers;
+						achievements.daily_misses = misses;
+					}
+				},
+				(Some("waveform"), 2) =>
+				{
+					match parts[1].parse::<usize>()
+					{
+						Ok(idx) if idx < 3 => achievements.waveforms[idx] = true,
+						_ => {},
+					}
+				},
+				_ => {},
+			}
+		}
+		return achievements;
+	}
+
+	pub fn save(&self, filename: &str) -> io::Result<()>
+	{
+		let mut file = try!(File::create(filename));
+		for achievement in ACHIEVEMENTS.iter()
+		{
+			if self.is_unlocked(*achievement)
+			{
+				try!(writeln!(file, "unlocked {}", achievement.name()));
+			}
+		}
+		try!(writeln!(file, "daily {} {} {}", self.daily_date, self.daily_answers, self.daily_misses));
+		for idx in 0..3
+		{
+			if self.waveforms[idx]
+			{
+				try!(writeln!(file, "waveform {}", idx));
+			}
+		}
+		Ok(())
+	}
+
+	pub fn is_unlocked(&self, achievement: Achievement) -> bool
+	{
+		self.unlocked[achievement as usize]
+	}
+
+	fn unlock(&mut self, achievement: Achievement, unlocked: &mut Vec<Achievement>)
+	{
+		if !self.is_unlocked(achievement)
+		{
+			self.unlocked[achievement as usize] = true;
+			unlocked.push(achievement);
+		}
+	}
+
+	// Record an answer to a round at level, after it has been added to streak,
+	// returning anything newly unlocked.
+	pub fn record(&mut self, target: &Sound, hit: bool, level: u32, streak: &Streak) -> Vec<Achievement>
+	{
+		self.record_on(today(), target, hit, level, streak)
+	}
+
+	// As record, answered on date.
+	fn record_on(&mut self, date: String, target: &Sound, hit: bool, level: u32, streak: &Streak) -> Vec<Achievement>
+	{
+		let mut unlocked = Vec::new();
+
+		if date != self.daily_date
+		{
+			self.daily_date = date;
+			self.daily_answers = 0;
+			self.daily_misses = 0;
+		}
+		self.daily_answers += 1;
+		if !hit
+		{
+			self.daily_misses += 1;
+			return unlocked;
+		}
+
+		if streak.current >= STREAK_GOAL
+		{
+			self.unlock(Achievement::Streak, &mut unlocked);
+		}
+		if level >= LEVEL_GOAL
+		{
+			self.unlock(Achievement::ClearLevel, &mut unlocked);
+		}
+		if self.daily_answers >= DAILY_ROUNDS && self.daily_misses == 0
+		{
+			self.unlock(Achievement::PerfectDaily, &mut unlocked);
+		}
+
+		let wave = target.dominant_wave();
+		if pitch::cents_between(WAVEFORM_FREQ, target.freqs[wave]).abs() <= WAVEFORM_TOLERANCE
+		{
+			self.waveforms[wave] = true;
+		}
+		if self.waveforms.iter().all(|&found| found)
+		{
+			self.unlock(Achievement::Waveforms, &mut unlocked);
+		}
+		return unlocked;
+	}
+
+	// Progress towards achievement, such as "12/20 TODAY", or empty once unlocked.
+	fn progress(&self, achievement: Achievement, streak: &Streak) -> String
+	{
+		if self.is_unlocked(achievement)
+		{
+			return String::new();
+		}
+		match achievement
+		{
+			Achievement::Streak => format!("BEST {}/{}", streak.best.min(STREAK_GOAL), STREAK_GOAL),
+			Achievement::ClearLevel => String::new(),
+			Achievement::PerfectDaily if self.daily_misses > 0 => "MISSED TODAY".to_string(),
+			Achievement::PerfectDaily => format!("{}/{} TODAY", self.daily_answers.min(DAILY_ROUNDS), DAILY_ROUNDS),
+			Achievement::Waveforms => format!("{}/3", self.waveforms.iter().filter(|&&found| found).count()),
+		}
+	}
+
+	// Draw over the game, which is dimmed if it can be.
+	pub fn draw(&self, renderer: &mut Renderer, lines: &mut LineBatch, streak: &Streak, theme: &Theme, screen_size: Vec2d) -> Result<(), String>
+	{
+		let dimmed = dim_background(renderer, theme, screen_size);
+
+		// Title and spacer, two rows per achievement, then the session streak.
+		let num_rows = 2 + ACHIEVEMENTS.len() * 2 + 1;
+		let row_height = (screen_size.y * 0.8 / num_rows as f32).min(48.0);
+		let scale = row_height * 0.25;
+		let label_x = screen_size.x * 0.5 - scale * 24.0;
+		let progress_x = screen_size.x * 0.5 + scale * 12.0;
+		let mut y = (screen_size.y - row_height * num_rows as f32) * 0.5;
+
+		draw_string(renderer, lines, Vec2d::new(label_x, y), scale * 1.5, theme.high_score, &"ACHIEVEMENTS".to_string());
+		y += row_height * 2.0;
+
+		for achievement in ACHIEVEMENTS.iter()
+		{
+			let unlocked = self.is_unlocked(*achievement);
+			let color = if unlocked { theme.hit } else { theme.shape };
+			let label = format!("{} {}", if unlocked { "+" } else { "-" }, achievement.title());
+			draw_string(renderer, lines, Vec2d::new(label_x - scale * 3.0, y), scale, color, &label);
+			draw_string(renderer, lines, Vec2d::new(progress_x, y), scale, theme.shape, &self.progress(*achievement, streak));
+			draw_string(renderer, lines, Vec2d::new(label_x, y + row_height * 0.75), scale * 0.6, theme.shape, &achievement.description());
+			y += row_height * 2.0;
+		}
+
+		draw_string(renderer, lines, Vec2d::new(label_x, y), scale, theme.score, &format!("STREAK {}  BEST {}", streak.current, streak.best));
+		return dimmed;
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::env;
+	use std::fs;
+	use std::process;
+	use super::*;
+
+	fn sound(wave: usize, freq: f32) -> Sound
+	{
+		let mut sound = Sound { freqs: [freq; 3], volumes: [0.0; 3] };
+		sound.volumes[wave] = 0.5;
+		return sound;
+	}
+
+	#[test]
+	fn streak_counts_milestones_and_best()
+	{
+		let mut streak = Streak::new();
+		for count in 1..(STREAK_MILESTONE * 2 + 1)
+		{
+			let milestone = streak.record(true);
+			assert_eq!(milestone, if count % STREAK_MILESTONE == 0 { Some(count) } else { None });
+		}
+		assert_eq!(streak.record(false), None);
+		assert_eq!(streak.current, 0);
+		assert_eq!(streak.best, STREAK_MILESTONE * 2);
+
+		streak.record(true);
+		assert_eq!(streak.current, 1);
+		assert_eq!(streak.best, STREAK_MILESTONE * 2);
+	}
+
+	#[test]
+	fn daily_count_rolls_over()
+	{
+		let mut achievements = Achievements::new();
+		let streak = Streak::new();
+		let target = sound(0, 440.0);
+		achievements.record_on("20260101".to_string(), &target, false, 1, &streak);
+		for _ in 0..DAILY_ROUNDS
+		{
+			let unlocked = achievements.record_on("20260101".to_string(), &target, true, 1, &streak);
+			assert!(unlocked.is_empty());
+		}
+		assert_eq!(achievements.daily_misses, 1);
+
+		// A new day forgets the miss.
+		for count in 1..(DAILY_ROUNDS + 1)
+		{
+			let unlocked = achievements.record_on("20260102".to_string(), &target, true, 1, &streak);
+			assert_eq!(achievements.daily_date, "20260102");
+			assert_eq!(achievements.daily_answers, count);
+			assert_eq!(achievements.daily_misses, 0);
+			assert_eq!(unlocked.is_empty(), count < DAILY_ROUNDS);
+		}
+		assert!(achievements.is_unlocked(Achievement::PerfectDaily));
+	}
+
+	#[test]
+	fn record_unlocks_once()
+	{
+		let mut achievements = Achievements::new();
+		let mut streak = Streak::new();
+		for _ in 0..STREAK_GOAL
+		{
+			streak.record(true);
+		}
+		let unlocked = achievements.record(&sound(0, 440.0), true, LEVEL_GOAL, &streak);
+		assert_eq!(unlocked, vec![Achievement::Streak, Achievement::ClearLevel]);
+		assert!(achievements.record(&sound(0, 440.0), true, LEVEL_GOAL, &streak).is_empty());
+
+		assert!(achievements.record(&sound(0, WAVEFORM_FREQ), true, 1, &streak).is_empty());
+		assert!(achievements.record(&sound(1, WAVEFORM_FREQ * 1.01), true, 1, &streak).is_empty());
+		// A miss doesn't count towards the waveforms.
+		assert!(achievements.record(&sound(2, WAVEFORM_FREQ), false, 1, &streak).is_empty());
+		assert_eq!(achievements.record(&sound(2, WAVEFORM_FREQ), true, 1, &streak), vec![Achievement::Waveforms]);
+	}
+
+	#[test]
+	fn save_and_load_round_trip()
+	{
+		let path = env::temp_dir().join(format!("oscillomatch-achievements-{}.txt", process::id()));
+		let filename = path.to_str().unwrap();
+
+		let mut achievements = Achievements::new();
+		let streak = Streak::new();
+		// Loading keeps the daily count only for the same day.
+		let date = "20260101".to_string();
+		achievements.record_on(date.clone(), &sound(1, WAVEFORM_FREQ), true, LEVEL_GOAL, &streak);
+		achievements.record_on(date.clone(), &sound(0, 440.0), false, 1, &streak);
+		achievements.save(filename).unwrap();
+
+		let loaded = Achievements::load_on(filename, date.clone());
+		let next_day = Achievements::load_on(filename, "20260102".to_string());
+		fs::remove_file(filename).unwrap();
+		assert_eq!(loaded.unlocked, achievements.unlocked);
+		assert_eq!(loaded.daily_date, date);
+		assert_eq!(loaded.daily_answers, 2);
+		assert_eq!(loaded.daily_misses, 1);
+		assert_eq!(loaded.waveforms, [false, true, false]);
+		assert!(loaded.is_unlocked(Achievement::ClearLevel));
+		assert_eq!(next_day.daily_answers, 0);
+		assert_eq!(next_day.unlocked, achievements.unlocked);
+
+		// Missing files start afresh.
+		let fresh = Achievements::load(filename);
+		assert_eq!(fresh.unlocked, [false; 4]);
+		assert_eq!(fresh.daily_answers, 0);
+	}
+}
@@ -0,0 +1,379 @@
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufWriter, SeekFrom};
+use std::fs::File;
+use sdl2::audio::{AudioCallback, AudioDevice, AudioFormat, AudioSpecDesired};
+use sdl2::AudioSubsystem;
+use mixer::Mixer;
+
+// Longest stretch of audio mixed in one update, so a stall doesn't
+// produce seconds of sound at once.
+const MAX_UPDATE_TIME: f32 = 0.25;
+// Frames mixed per call to the mixer by backends that drive it themselves.
+const MIX_FRAMES: usize = 512;
+
+/////////////////////////////////////////////////////////////////////
+// BackendKind
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BackendKind
+{
+	// Play through the sound card.
+	Sdl,
+	// Mix and discard, for machines without sound.
+	Null,
+	// Record to a WAV file instead of playing.
+	Capture,
+}
+
+impl BackendKind
+{
+	// Name used in the config file.
+	pub fn name(&self) -> &'static str
+	{
+		match *self
+		{
+			BackendKind::Sdl => "sdl",
+			BackendKind::Null => "null",
+			BackendKind::Capture => "capture",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<BackendKind>
+	{
+		match name
+		{
+			"sdl" => Some(BackendKind::Sdl),
+			"null" => Some(BackendKind::Null),
+			"capture" => Some(BackendKind::Capture),
+			_ => None,
+		}
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// AudioBackend
+// Where mixer output goes. Backends without a device of their own run the
+// mixer from update, keeping pace with the game's clock.
+pub trait AudioBackend
+{
+	// Description for messages, such as "SDL playback".
+	fn name(&self) -> String;
+
+	// Called once a frame with the seconds since the last one.
+	fn update(&mut self, tick: f32);
+}
+
+// Mix tick seconds of audio a block at a time, passing each block to write.
+fn mix_for<F: FnMut(&[f32])>(mixer: &mut Mixer, buffer: &mut Vec<f32>, pending: &mut f32, tick: f32, mut write: F)
+{
+	*pending += tick.max(0.0).min(MAX_UPDATE_TIME) * mixer.freq() as f32;
+	let num_channels = mixer.num_channels();
+	while *pending >= 1.0
+	{
+		let frames = (*pending as usize).min(MIX_FRAMES);
+		*pending -= frames as f32;
+		buffer.resize(frames * num_channels, 0.0);
+		mixer.mix(buffer);
+		write(buffer);
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// SdlBackend
+struct SdlCallback
+{
+	mixer: Mixer,
+}
+
+impl AudioCallback for SdlCallback
+{
+	type Channel = f32;
+	fn callback(&mut self, out: &mut [f32])
+	{
+		self.mixer.mix(out);
+	}
+}
+
+pub struct SdlBackend
+{
+	// Kept open while playing.
+	_device: AudioDevice<SdlCallback>,
+	// Format the device was opened with, which may differ from the one asked for.
+	freq: i32,
+	format: AudioFormat,
+	num_channels: u8,
+}
+
+impl SdlBackend
+{
+	// Open and start playback, handing the mixer back on failure so
+	// another backend can use it.
+	pub fn open(audio_ctx: &AudioSubsystem, device_name: Option<&str>, spec: &AudioSpecDesired, mixer: Mixer) -> Result<SdlBackend, (String, Mixer)>
+	{
+		let mut mixer = Some(mixer);
+		let mut obtained = None;
+		let result = audio_ctx.open_playback(device_name, spec, |spec|
+		{
+			obtained = Some((spec.freq, spec.format, spec.channels));
+			let mut mixer = mixer.take().unwrap();
+			mixer.set_format(spec.freq, spec.channels as usize);
+			SdlCallback
+			{
+				mixer: mixer,
+			}
+		});
+		match result
+		{
+			Ok(device) =>
+			{
+				device.resume();
+				let (freq, format, num_channels) = obtained.unwrap();
+				Ok(SdlBackend
+				{
+					_device: device,
+					freq: freq,
+					format: format,
+					num_channels: num_channels,
+				})
+			},
+			Err(err) => Err((err, mixer.take().unwrap())),
+		}
+	}
+}
+
+impl AudioBackend for SdlBackend
+{
+	fn name(&self) -> String
+	{
+		format!("SDL playback at {} Hz, {} channels of {:?}", self.freq, self.num_channels, self.format)
+	}
+
+	// The device pulls audio on its own thread.
+	fn update(&mut self, _tick: f32)
+	{
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// NullBackend
+// Runs the mixer so the scope and message queue behave as with sound.
+pub struct NullBackend
+{
+	mixer: Mixer,
+	buffer: Vec<f32>,
+	// Frames owed but not yet mixed.
+	pending: f32,
+}
+
+impl NullBackend
+{
+	pub fn new(mixer: Mixer) -> NullBackend
+	{
+		NullBackend
+		{
+			mixer: mixer,
+			buffer: Vec::new(),
+			pending: 0.0,
+		}
+	}
+}
+
+impl AudioBackend for NullBackend
+{
+	fn name(&self) -> String
+	{
+		"no sound".to_string()
+	}
+
+	fn update(&mut self, tick: f32)
+	{
+		mix_for(&mut self.mixer, &mut self.buffer, &mut self.pending, tick, |_| {});
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// CaptureBackend
+// Writes 16 bit PCM WAV. The header sizes are filled in when dropped.
+pub struct CaptureBackend
+{
+	mixer: Mixer,
+	buffer: Vec<f32>,
+	pending: f32,
+	filename: String,
+	file: BufWriter<File>,
+	// Bytes of sample data written.
+	data_size: u32,
+	// First write error, after which capture stops.
+	failed: bool,
+}
+
+impl CaptureBackend
+{
+	pub fn create(filename: &str, mut mixer: Mixer) -> Result<CaptureBackend, (String, Mixer)>
+	{
+		let freq = mixer.freq();
+		mixer.set_format(freq, 2);
+		let mut file = match File::create(filename)
+		{
+			Ok(file) => BufWriter::new(file),
+			Err(err) => return Err((format!("can't create {}: {}", filename, err), mixer)),
+		};
+		if let Err(err) = write_wav_header(&mut file, freq as u32, 2, 0)
+		{
+			return Err((format!("can't write {}: {}", filename, err), mixer));
+		}
+		Ok(CaptureBackend
+		{
+			mixer: mixer,
+			buffer: Vec::new(),
+			pending: 0.0,
+			filename: filename.to_string(),
+			file: file,
+			data_size: 0,
+			failed: false,
+		})
+	}
+
+	fn finish(&mut self) -> io::Result<()>
+	{
+		let freq = self.mixer.freq() as u32;
+		try!(self.file.seek(SeekFrom::Start(0)));
+		try!(write_wav_header(&mut self.file, freq, 2, self.data_size));
+		self.file.flush()
+	}
+}
+
+impl AudioBackend for CaptureBackend
+{
+	fn name(&self) -> String
+	{
+		format!("capture to {}", self.filename)
+	}
+
+	fn update(&mut self, tick: f32)
+	{
+		if self.failed
+		{
+			return;
+		}
+
+		let file = &mut self.file;
+		let data_size = &mut self.data_size;
+		let mut result = Ok(());
+		mix_for(&mut self.mixer, &mut self.buffer, &mut self.pending, tick, |samples|
+		{
+			if result.is_err()
+			{
+				return;
+			}
+			result = write_samples(file, samples);
+			*data_size += samples.len() as u32 * 2;
+		});
+
+		if let Err(err) = result
+		{
+			writeln!(io::stderr(), "Warning: Capture to {} stopped: {}", self.filename, err).unwrap();
+			self.failed = true;
+		}
+	}
+}
+
+impl Drop for CaptureBackend
+{
+	fn drop(&mut self)
+	{
+		if let Err(err) = self.finish()
+		{
+			writeln!(io::stderr(), "Warning: Failed to finish {}: {}", self.filename, err).unwrap();
+		}
+	}
+}
+
+// Write interleaved samples as a whole 16 bit PCM WAV file.
+pub fn write_wav<W: Write>(out: &mut W, freq: u32, num_channels: u16, samples: &[f32]) -> io::Result<()>
+{
+	try!(write_wav_header(out, freq, num_channels, samples.len() as u32 * 2));
+	write_samples(out, samples)
+}
+
+fn write_samples<W: Write>(out: &mut W, samples: &[f32]) -> io::Result<()>
+{
+	for &sample in samples
+	{
+		let value = (sample.max(-1.0).min(1.0) * 32767.0) as i16;
+		try!(write_u16(out, value as u16));
+	}
+	return Ok(());
+}
+
+fn write_u16<W: Write>(out: &mut W, value: u16) -> io::Result<()>
+{
+	out.write_all(&[value as u8, (value >> 8) as u8])
+}
+
+fn write_u32<W: Write>(out: &mut W, value: u32) -> io::Result<()>
+{
+	out.write_all(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8])
+}
+
+// RIFF header for 16 bit PCM followed by data_size bytes of samples.
+fn write_wav_header<W: Write>(out: &mut W, freq: u32, num_channels: u16, data_size: u32) -> io::Result<()>
+{
+	let block_align = num_channels * 2;
+	try!(out.write_all(b"RIFF"));
+	try!(write_u32(out, 36 + data_size));
+	try!(out.write_all(b"WAVEfmt "));
+	try!(write_u32(out, 16));
+	// PCM.
+	try!(write_u16(out, 1));
+	try!(write_u16(out, num_channels));
+	try!(write_u32(out, freq));
+	try!(write_u32(out, freq * block_align as u32));
+	try!(write_u16(out, block_align));
+	try!(write_u16(out, 16));
+	try!(out.write_all(b"data"));
+	write_u32(out, data_size)
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn read_u16(bytes: &[u8], offset: usize) -> u16
+	{
+		bytes[offset] as u16 | (bytes[offset + 1] as u16) << 8
+	}
+
+	fn read_u32(bytes: &[u8], offset: usize) -> u32
+	{
+		read_u16(bytes, offset) as u32 | (read_u16(bytes, offset + 2) as u32) << 16
+	}
+
+	#[test]
+	fn write_wav_round_trip()
+	{
+		let samples = [0.0, 0.5, -0.5, 1.0, -1.0, 2.0];
+		let mut bytes = Vec::new();
+		write_wav(&mut bytes, 22050, 2, &samples).unwrap();
+
+		assert_eq!(&bytes[0..4], b"RIFF");
+		assert_eq!(read_u32(&bytes, 4) as usize, bytes.len() - 8);
+		assert_eq!(&bytes[8..16], b"WAVEfmt ");
+		assert_eq!(read_u16(&bytes, 20), 1);
+		assert_eq!(read_u16(&bytes, 22), 2);
+		assert_eq!(read_u32(&bytes, 24), 22050);
+		assert_eq!(read_u32(&bytes, 28), 22050 * 4);
+		assert_eq!(read_u16(&bytes, 32), 4);
+		assert_eq!(read_u16(&bytes, 34), 16);
+		assert_eq!(&bytes[36..40], b"data");
+		assert_eq!(read_u32(&bytes, 40) as usize, samples.len() * 2);
+
+		// Samples come back to within a step of 16 bits, clipped to -1 to 1.
+		let read: Vec<f32> = (0..samples.len()).map(|idx| read_u16(&bytes, 44 + idx * 2) as i16 as f32 / 32767.0).collect();
+		for (idx, &sample) in samples.iter().enumerate()
+		{
+			assert!((read[idx] - sample.max(-1.0).min(1.0)).abs() < 1.0 / 32767.0, "sample {}: {} read as {}", idx, sample, read[idx]);
+		}
+	}
+}
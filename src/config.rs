@@ -0,0 +1,710 @@
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::fs::File;
+use sdl2::keyboard::Keycode;
+use audio::BackendKind;
+use input::{Action, Bindings, ACTIONS};
+use pitch;
+use pitch::{Temperament, Tuning};
+use puzzle::Family;
+use theme::Theme;
+
+pub const DEFAULT_CONFIG_FILENAME: &'static str = "oscillomatch.cfg";
+
+pub const USAGE: &'static str = "Usage: oscillomatch [options]
+
+Options:
+  --config FILE        Read settings from FILE instead of oscillomatch.cfg
+  --width PIXELS       Window width
+  --height PIXELS      Window height
+  --fullscreen         Run fullscreen
+  --windowed           Run in a window
+  --vsync              Wait for vertical sync
+  --no-vsync           Don't wait for vertical sync
+  --audio-device NAME  Play through the named audio device
+  --audio-backend NAME Sound output: sdl, null or capture
+  --capture FILE       Record sound to FILE as WAV instead of playing it
+  --sample-rate HZ     Audio sample rate
+  --buffer-size FRAMES Audio buffer size, a power of two
+  --volume AMOUNT      Master volume from 0 to 1
+  --music AMOUNT       Background music volume from 0 to 1
+  --level LEVEL        Starting level
+  --mode MODE          Puzzle family: mixed, timbre, lissajous or harmony
+  --tuning TUNING      Interval and chord tuning: equal, just or pythagorean
+  --seed SEED          Seed for round generation
+  --theme NAME         Colour theme
+  --midi-out PORT      Send tones as MIDI notes to PORT, or virtual
+  --midi-in PORT       Pick shapes with MIDI pads on PORT, or virtual
+  --bench              Time shape updates and exit
+  --help               Show this message";
+
+// Command line flags, with the setting each overrides. Flags with a fixed
+// value take no argument.
+const FLAGS: [(&'static str, &'static str, &'static str, Option<&'static str>); 19] =
+[
+	("--width", "display", "width", None),
+	("--height", "display", "height", None),
+	("--fullscreen", "display", "fullscreen", Some("true")),
+	("--windowed", "display", "fullscreen", Some("false")),
+	("--vsync", "display", "vsync", Some("true")),
+	("--no-vsync", "display", "vsync", Some("false")),
+	("--audio-device", "audio", "device", None),
+	("--audio-backend", "audio", "backend", None),
+	("--sample-rate", "audio", "sample_rate", None),
+	("--buffer-size", "audio", "buffer_size", None),
+	("--volume", "audio", "master_volume", None),
+	("--music", "audio", "music_volume", None),
+	("--level", "gameplay", "level", None),
+	("--mode", "gameplay", "mode", None),
+	("--tuning", "gameplay", "tuning", None),
+	("--seed", "gameplay", "seed", None),
+	("--theme", "theme", "name", None),
+	("--midi-out", "midi", "output", None),
+	("--midi-in", "midi", "input", None),
+];
+
+/////////////////////////////////////////////////////////////////////
+// ConfigError
+#[derive(Clone, Debug)]
+pub struct ConfigError
+{
+	// Where the bad setting came from, such as "oscillomatch.cfg:12".
+	pub source: String,
+	pub message: String,
+}
+
+impl ConfigError
+{
+	fn new(in_source: &str, in_message: String) -> ConfigError
+	{
+		ConfigError
+		{
+			source: in_source.to_string(),
+			message: in_message,
+		}
+	}
+}
+
+impl fmt::Display for ConfigError
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		write!(f, "{}: {}", self.source, self.message)
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// CommandLine
+pub struct CommandLine
+{
+	pub config_filename: String,
+	pub bench: bool,
+	pub help: bool,
+	// Settings given on the command line, as section, key and value.
+	pub overrides: Vec<(&'static str, &'static str, String)>,
+}
+
+impl CommandLine
+{
+	pub fn parse(args: &[String]) -> Result<CommandLine, ConfigError>
+	{
+		let mut command_line = CommandLine
+		{
+			config_filename: DEFAULT_CONFIG_FILENAME.to_string(),
+			bench: false,
+			help: false,
+			overrides: Vec::new(),
+		};
+
+		let mut idx = 0;
+		while idx < args.len()
+		{
+			let arg = &args[idx];
+			idx += 1;
+			match arg.as_ref()
+			{
+				"--help" | "-h" => command_line.help = true,
+				"--bench" => command_line.bench = true,
+				"--config" =>
+				{
+					command_line.config_filename = try!(flag_value(args, idx, arg));
+					idx += 1;
+				},
+				"--capture" =>
+				{
+					let filename = try!(flag_value(args, idx, arg));
+					idx += 1;
+					command_line.overrides.push(("audio", "backend", "capture".to_string()));
+					command_line.overrides.push(("audio", "capture_file", filename));
+				},
+				_ =>
+				{
+					let flag = match FLAGS.iter().find(|flag| flag.0 == arg)
+					{
+						Some(flag) => flag,
+						None => return Err(ConfigError::new("command line", format!("unknown option '{}', see --help", arg))),
+					};
+					let value = match flag.3
+					{
+						Some(value) => value.to_string(),
+						None =>
+						{
+							let value = try!(flag_value(args, idx, arg));
+							idx += 1;
+							value
+						},
+					};
+					command_line.overrides.push((flag.1, flag.2, value));
+				},
+			}
+		}
+		return Ok(command_line);
+	}
+}
+
+fn flag_value(args: &[String], idx: usize, flag: &str) -> Result<String, ConfigError>
+{
+	match args.get(idx)
+	{
+		Some(value) => Ok(value.clone()),
+		None => Err(ConfigError::new("command line", format!("{} needs a value", flag))),
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// MidiPort
+#[derive(Clone, Debug, PartialEq)]
+pub enum MidiPort
+{
+	None,
+	// A port other programs can connect to.
+	Virtual,
+	// First port whose name contains this.
+	Named(String),
+}
+
+impl MidiPort
+{
+	fn parse(value: &str) -> MidiPort
+	{
+		match value
+		{
+			"" | "none" => MidiPort::None,
+			"virtual" => MidiPort::Virtual,
+			_ => MidiPort::Named(value.to_string()),
+		}
+	}
+
+	fn name(&self) -> &str
+	{
+		match *self
+		{
+			MidiPort::None => "none",
+			MidiPort::Virtual => "virtual",
+			MidiPort::Named(ref name) => name,
+		}
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// Config
+// Settings file sections and keys:
+//   [display]  title, width, height, fullscreen, vsync, scanlines, noise
+//   [audio]    backend, capture_file, device, sample_rate, buffer_size, master_volume, tone_volume, sfx_volume, music_volume
+//   [gameplay] level, mode, seed, tuning, reference
+//   [theme]    name, plus any Theme colour as "r g b"
+//   [input]    replay, preview, hint, options, export, achievements as SDL key names
+//   [midi]     output, input as none, virtual or a port name, first_pad
+#[derive(Clone)]
+pub struct Config
+{
+	// Display.
+	pub title: String,
+	pub width: u32,
+	pub height: u32,
+	pub fullscreen: bool,
+	pub vsync: bool,
+	// How much scanlines darken, from 0 to 1.
+	pub scanlines: f32,
+	// Amount of screen noise, from 0 to 1.
+	pub noise: f32,
+	// Audio.
+	pub audio_backend: BackendKind,
+	// WAV file written by the capture backend.
+	pub capture_file: String,
+	// Playback device name, or None for the default.
+	pub audio_device: Option<String>,
+	pub sample_rate: i32,
+	// Frames per audio buffer, or None to let SDL choose.
+	pub buffer_size: Option<u16>,
+	// Volume of everything, target tones, feedback sounds and music, from 0 to 1.
+	pub master_volume: f32,
+	pub tone_volume: f32,
+	pub sfx_volume: f32,
+	pub music_volume: f32,
+	// Gameplay.
+	pub start_level: u32,
+	// Puzzle family to play, or None to mix them by level.
+	pub mode: Option<Family>,
+	// Seed for round generation, or None for a random one.
+	pub seed: Option<u32>,
+	// Tuning of interval and chord rounds.
+	pub tuning: Tuning,
+	pub theme: Theme,
+	pub bindings: Bindings,
+	// MIDI.
+	// Where tones are sent as notes, and where pads are read from.
+	pub midi_output: MidiPort,
+	pub midi_input: MidiPort,
+	// Note of the pad that picks the first shape. The rest follow upwards.
+	pub midi_first_pad: i32,
+}
+
+impl Config
+{
+	pub fn default() -> Config
+	{
+		Config
+		{
+			title: "Oscillomatch by NeiloGD".to_string(),
+			width: 1024,
+			height: 768,
+			fullscreen: false,
+			vsync: false,
+			scanlines: 0.125,
+			noise: 1.0,
+			audio_backend: BackendKind::Sdl,
+			capture_file: "capture.wav".to_string(),
+			audio_device: None,
+			sample_rate: 44100,
+			buffer_size: None,
+			master_volume: 1.0,
+			tone_volume: 1.0,
+			sfx_volume: 1.0,
+			music_volume: 0.5,
+			start_level: 1,
+			mode: None,
+			seed: None,
+			tuning: Tuning::equal(),
+			theme: Theme::green(),
+			bindings: Bindings::default(),
+			midi_output: MidiPort::None,
+			midi_input: MidiPort::None,
+			// C2, the first pad on most drum controllers.
+			midi_first_pad: 36,
+		}
+	}
+
+	// Load from an INI style file, using defaults for anything missing.
+	pub fn load(filename: &str) -> Result<Config, ConfigError>
+	{
+		let config = Config::default();
+		let mut s = String::new();
+		match File::open(filename)
+		{
+			Ok(mut file) =>
+			{
+				if let Err(err) = file.read_to_string(&mut s)
+				{
+					return Err(ConfigError::new(filename, format!("can't read file: {}", err)));
+				}
+			}
+			Err(..) => return Ok(config),
+		};
+		return Config::parse(filename, &s);
+	}
+
+	// Settings from the text of a config file, reported against filename.
+	fn parse(filename: &str, s: &str) -> Result<Config, ConfigError>
+	{
+		let mut config = Config::default();
+		let mut section = String::new();
+		let mut settings = Vec::new();
+		for (line_idx, line) in s.lines().enumerate()
+		{
+			let source = format!("{}:{}", filename, line_idx + 1);
+			let line = line.split(|c| c == '#' || c == ';').next().unwrap_or("").trim();
+			if line.is_empty()
+			{
+				continue;
+			}
+
+			if line.starts_with('[')
+			{
+				if !line.ends_with(']')
+				{
+					return Err(ConfigError::new(&source, format!("section header '{}' is missing ']'", line)));
+				}
+				section = line[1..line.len() - 1].trim().to_string();
+				continue;
+			}
+
+			let mut parts = line.splitn(2, '=');
+			match (parts.next(), parts.next())
+			{
+				(Some(key), Some(value)) => settings.push((source, section.clone(), key.trim().to_string(), value.trim().to_string())),
+				_ => return Err(ConfigError::new(&source, format!("expected 'key = value', found '{}'", line))),
+			}
+		}
+
+		// Pick the base theme first, so colour overrides apply on top of it.
+		settings.sort_by_key(|setting| !(setting.1 == "theme" && setting.2 == "name"));
+		for &(ref source, ref section, ref key, ref value) in settings.iter()
+		{
+			try!(config.set(section, key, value).map_err(|message| ConfigError::new(source, message)));
+		}
+		return Ok(config);
+	}
+
+	// Apply settings from the command line over those loaded.
+	pub fn apply(&mut self, command_line: &CommandLine) -> Result<(), ConfigError>
+	{
+		for &(section, key, ref value) in command_line.overrides.iter()
+		{
+			try!(self.set(section, key, value).map_err(|message| ConfigError::new("command line", message)));
+		}
+		return Ok(());
+	}
+
+	// Validate and set one setting.
+	pub fn set(&mut self, section: &str, key: &str, value: &str) -> Result<(), String>
+	{
+		match (section, key)
+		{
+			("display", "title") => self.title = value.to_string(),
+			("display", "width") => self.width = try!(parse_range(key, value, 320, 7680)),
+			("display", "height") => self.height = try!(parse_range(key, value, 240, 4320)),
+			("display", "fullscreen") => self.fullscreen = try!(parse_bool(key, value)),
+			("display", "vsync") => self.vsync = try!(parse_bool(key, value)),
+			("display", "scanlines") => self.scanlines = try!(parse_fraction(key, value)),
+			("display", "noise") => self.noise = try!(parse_fraction(key, value)),
+			("audio", "backend") =>
+			{
+				self.audio_backend = try!(BackendKind::from_name(value).ok_or(format!("backend must be sdl, null or capture, got '{}'", value)));
+			},
+			("audio", "capture_file") =>
+			{
+				if value.is_empty()
+				{
+					return Err("capture_file needs a file name".to_string());
+				}
+				self.capture_file = value.to_string();
+			},
+			("audio", "device") => self.audio_device = if value.is_empty() || value == "default" { None } else { Some(value.to_string()) },
+			("audio", "sample_rate") => self.sample_rate = try!(parse_range(key, value, 8000, 192000)) as i32,
+			("audio", "buffer_size") =>
+			{
+				let size = try!(parse_range(key, value, 64, 8192));
+				if !size.is_power_of_two()
+				{
+					return Err(format!("buffer_size must be a power of two, got {}", size));
+				}
+				self.buffer_size = Some(size as u16);
+			},
+			("audio", "master_volume") => self.master_volume = try!(parse_fraction(key, value)),
+			("audio", "tone_volume") => self.tone_volume = try!(parse_fraction(key, value)),
+			("audio", "sfx_volume") => self.sfx_volume = try!(parse_fraction(key, value)),
+			("audio", "music_volume") => self.music_volume = try!(parse_fraction(key, value)),
+			("gameplay", "level") => self.start_level = try!(parse_range(key, value, 1, 999)),
+			("gameplay", "mode") =>
+			{
+				self.mode = match value
+				{
+					"mixed" => None,
+					"timbre" => Some(Family::Timbre),
+					"lissajous" => Some(Family::Lissajous),
+					"harmony" => Some(Family::Harmony),
+					_ => return Err(format!("mode must be mixed, timbre, lissajous or harmony, got '{}'", value)),
+				};
+			},
+			("gameplay", "seed") =>
+			{
+				self.seed = if value.is_empty() || value == "random"
+				{
+					None
+				}
+				else
+				{
+					Some(try!(value.parse::<u32>().map_err(|_| format!("seed must be a whole number or random, got '{}'", value))))
+				};
+			},
+			("gameplay", "tuning") =>
+			{
+				self.tuning.temperament = try!(Temperament::from_name(value).ok_or(format!("tuning must be equal, just or pythagorean, got '{}'", value)));
+			},
+			("gameplay", "reference") =>
+			{
+				self.tuning.reference = match value.parse::<f32>()
+				{
+					Ok(freq) if freq >= 400.0 && freq <= 480.0 => freq,
+					_ => return Err(format!("reference must be the frequency of A4 from 400 to 480, got '{}'", value)),
+				};
+			},
+			("theme", "name") =>
+			{
+				self.theme = try!(Theme::by_name(value).ok_or(format!("unknown theme '{}'", value)));
+			},
+			("theme", _) => try!(self.theme.set(key, value)),
+			("input", _) =>
+			{
+				let action = try!(Action::from_name(key).ok_or(format!("unknown action '{}' in [input]", key)));
+				let keycode = try!(Keycode::from_name(value).ok_or(format!("unknown key '{}' for {}", value, key)));
+				self.bindings.set(action, keycode);
+			},
+			("midi", "output") => self.midi_output = MidiPort::parse(value),
+			("midi", "input") => self.midi_input = MidiPort::parse(value),
+			("midi", "first_pad") =>
+			{
+				self.midi_first_pad = match value.parse::<i32>().ok().or(pitch::parse_note(value))
+				{
+					Some(note) if note >= 0 && note <= 127 => note,
+					_ => return Err(format!("first_pad must be a note name like C2 or a number from 0 to 127, got '{}'", value)),
+				};
+			},
+			("display", _) | ("audio", _) | ("gameplay", _) | ("midi", _) => return Err(format!("unknown setting '{}' in [{}]", key, section)),
+			_ => return Err(format!("unknown section [{}]", section)),
+		}
+		return Ok(());
+	}
+
+	// Write every setting, replacing the file. Comments are not kept.
+	pub fn save(&self, filename: &str) -> io::Result<()>
+	{
+		let mut file = try!(File::create(filename));
+		try!(writeln!(file, "[display]"));
+		try!(writeln!(file, "title = {}", self.title));
+		try!(writeln!(file, "width = {}", self.width));
+		try!(writeln!(file, "height = {}", self.height));
+		try!(writeln!(file, "fullscreen = {}", self.fullscreen));
+		try!(writeln!(file, "vsync = {}", self.vsync));
+		try!(writeln!(file, "scanlines = {}", self.scanlines));
+		try!(writeln!(file, "noise = {}", self.noise));
+
+		try!(writeln!(file, "\n[audio]"));
+		try!(writeln!(file, "backend = {}", self.audio_backend.name()));
+		try!(writeln!(file, "capture_file = {}", self.capture_file));
+		try!(writeln!(file, "device = {}", self.audio_device.as_ref().map_or("default", |name| name.as_ref())));
+		try!(writeln!(file, "sample_rate = {}", self.sample_rate));
+		if let Some(buffer_size) = self.buffer_size
+		{
+			try!(writeln!(file, "buffer_size = {}", buffer_size));
+		}
+		try!(writeln!(file, "master_volume = {}", self.master_volume));
+		try!(writeln!(file, "tone_volume = {}", self.tone_volume));
+		try!(writeln!(file, "sfx_volume = {}", self.sfx_volume));
+		try!(writeln!(file, "music_volume = {}", self.music_volume));
+
+		try!(writeln!(file, "\n[gameplay]"));
+		try!(writeln!(file, "level = {}", self.start_level));
+		let mode = match self.mode
+		{
+			None => "mixed",
+			Some(Family::Timbre) => "timbre",
+			Some(Family::Lissajous) => "lissajous",
+			Some(Family::Harmony) => "harmony",
+		};
+		try!(writeln!(file, "mode = {}", mode));
+		match self.seed
+		{
+			Some(seed) => try!(writeln!(file, "seed = {}", seed)),
+			None => try!(writeln!(file, "seed = random")),
+		}
+		try!(writeln!(file, "tuning = {}", self.tuning.temperament.name()));
+		try!(writeln!(file, "reference = {}", self.tuning.reference));
+
+		// Only colours changed from the named theme.
+		try!(writeln!(file, "\n[theme]"));
+		try!(writeln!(file, "name = {}", self.theme.name));
+		let base = Theme::by_name(self.theme.name).unwrap_or(Theme::green());
+		for (&(key, color), &(_, base_color)) in self.theme.colors().iter().zip(base.colors().iter())
+		{
+			let (r, g, b) = color.rgb();
+			if (r, g, b) != base_color.rgb()
+			{
+				try!(writeln!(file, "{} = {} {} {}", key, r, g, b));
+			}
+		}
+
+		try!(writeln!(file, "\n[input]"));
+		for action in ACTIONS.iter()
+		{
+			try!(writeln!(file, "{} = {}", action.name(), self.bindings.key(*action).name()));
+		}
+
+		try!(writeln!(file, "\n[midi]"));
+		try!(writeln!(file, "output = {}", self.midi_output.name()));
+		try!(writeln!(file, "input = {}", self.midi_input.name()));
+		try!(writeln!(file, "first_pad = {}", pitch::note_name(self.midi_first_pad)));
+		return Ok(());
+	}
+
+	// Save settings changed in the options menu into the file, leaving
+	// the rest as they were in it rather than as overridden on the command line.
+	pub fn save_options(&self, filename: &str) -> io::Result<()>
+	{
+		let mut saved = match Config::load(filename)
+		{
+			Ok(config) => config,
+			Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err.to_string())),
+		};
+		saved.fullscreen = self.fullscreen;
+		saved.scanlines = self.scanlines;
+		saved.noise = self.noise;
+		saved.master_volume = self.master_volume;
+		saved.tone_volume = self.tone_volume;
+		saved.sfx_volume = self.sfx_volume;
+		saved.music_volume = self.music_volume;
+		saved.theme = self.theme;
+		saved.bindings = self.bindings;
+		saved.save(filename)
+	}
+}
+
+fn parse_fraction(key: &str, value: &str) -> Result<f32, String>
+{
+	match value.parse::<f32>()
+	{
+		Ok(number) if number >= 0.0 && number <= 1.0 => Ok(number),
+		_ => Err(format!("{} must be a number from 0 to 1, got '{}'", key, value)),
+	}
+}
+
+fn parse_range(key: &str, value: &str, min: u32, max: u32) -> Result<u32, String>
+{
+	match value.parse::<u32>()
+	{
+		Ok(number) if number >= min && number <= max => Ok(number),
+		_ => Err(format!("{} must be a whole number from {} to {}, got '{}'", key, min, max, value)),
+	}
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String>
+{
+	match value
+	{
+		"true" | "yes" | "on" | "1" => Ok(true),
+		"false" | "no" | "off" | "0" => Ok(false),
+		_ => Err(format!("{} must be true or false, got '{}'", key, value)),
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::env;
+	use std::fs;
+	use std::process;
+	use puzzle::Family;
+	use super::*;
+
+	fn args(list: &[&str]) -> Vec<String>
+	{
+		list.iter().map(|arg| arg.to_string()).collect()
+	}
+
+	fn parse_error(text: &str) -> ConfigError
+	{
+		match Config::parse("test.cfg", text)
+		{
+			Ok(..) => panic!("'{}' should not parse", text),
+			Err(err) => err,
+		}
+	}
+
+	#[test]
+	fn good_config_is_applied()
+	{
+		let text = "# Comment\n[display]\nwidth = 1280\nheight = 720 ; trailing comment\nfullscreen = yes\n\n[audio]\ndevice = default\nsample_rate = 48000\nbuffer_size = 512\nmaster_volume = 0.5\n\n[gameplay]\nlevel = 3\nmode = lissajous\nseed = 42\n\n[theme]\nnoise = 1 2 3\nname = amber\n";
+		let config = Config::parse("test.cfg", text).unwrap();
+		assert_eq!((config.width, config.height), (1280, 720));
+		assert!(config.fullscreen);
+		assert!(config.audio_device.is_none());
+		assert_eq!(config.sample_rate, 48000);
+		assert_eq!(config.buffer_size, Some(512));
+		assert_eq!(config.master_volume, 0.5);
+		assert_eq!(config.start_level, 3);
+		assert_eq!(config.mode, Some(Family::Lissajous));
+		assert_eq!(config.seed, Some(42));
+		// The colour applies over the named theme, wherever the name appears.
+		assert_eq!(config.theme.name, "amber");
+		assert_eq!(config.theme.noise.rgb(), (1, 2, 3));
+	}
+
+	#[test]
+	fn unknown_keys_and_sections_are_errors()
+	{
+		let err = parse_error("[display]\nwidht = 1280\n");
+		assert_eq!(err.source, "test.cfg:2");
+		assert!(err.message.contains("unknown setting 'widht'"));
+		assert!(parse_error("[sound]\nvolume = 1\n").message.contains("unknown section [sound]"));
+		assert!(parse_error("[display\n").message.contains("missing ']'"));
+		assert!(parse_error("[display]\nfullscreen\n").message.contains("key = value"));
+	}
+
+	#[test]
+	fn bad_values_are_errors()
+	{
+		assert!(parse_error("[display]\nfullscreen = maybe\n").message.contains("true or false"));
+		assert!(parse_error("[display]\nwidth = wide\n").message.contains("whole number"));
+		assert!(parse_error("[audio]\nmaster_volume = loud\n").message.contains("must be a number"));
+		assert!(parse_error("[gameplay]\nmode = chess\n").message.contains("mixed, timbre, lissajous or harmony"));
+		assert!(parse_error("[gameplay]\nseed = -1\n").message.contains("whole number or random"));
+		assert!(parse_error("[theme]\nname = purple\n").message.contains("unknown theme"));
+		assert!(parse_error("[input]\nfly = F\n").message.contains("unknown action 'fly'"));
+		assert!(parse_error("[theme]\nnoise = 1 2\n").message.contains("three numbers"));
+	}
+
+	#[test]
+	fn out_of_range_values_are_errors()
+	{
+		assert!(parse_error("[display]\nwidth = 100\n").message.contains("from 320 to 7680"));
+		assert!(parse_error("[audio]\nsample_rate = 1000000\n").message.contains("from 8000 to 192000"));
+		assert!(parse_error("[audio]\nmaster_volume = 2\n").message.contains("from 0 to 1"));
+		assert!(parse_error("[audio]\nbuffer_size = 100\n").message.contains("power of two"));
+		assert!(parse_error("[gameplay]\nlevel = 0\n").message.contains("from 1 to 999"));
+	}
+
+	#[test]
+	fn load_reads_file_or_uses_defaults()
+	{
+		let path = env::temp_dir().join(format!("oscillomatch-config-test-{}.cfg", process::id()));
+		let filename = path.to_str().unwrap().to_string();
+		let config = Config::load(&filename).unwrap();
+		assert_eq!(config.width, Config::default().width);
+
+		fs::write(&path, "[display]\nwidth = 800\nheight = 0\n").unwrap();
+		let err = Config::load(&filename).err().unwrap();
+		fs::remove_file(&path).unwrap();
+		assert_eq!(err.source, format!("{}:3", filename));
+	}
+
+	#[test]
+	fn command_line_overrides_config()
+	{
+		let command_line = CommandLine::parse(&args(&["--config", "other.cfg", "--width", "800", "--fullscreen", "--mode", "timbre", "--bench"])).unwrap();
+		assert_eq!(command_line.config_filename, "other.cfg");
+		assert!(command_line.bench);
+		assert!(!command_line.help);
+
+		let mut config = Config::default();
+		config.apply(&command_line).unwrap();
+		assert_eq!(config.width, 800);
+		assert!(config.fullscreen);
+		assert_eq!(config.mode, Some(Family::Timbre));
+
+		let command_line = CommandLine::parse(&args(&["--volume", "3"])).unwrap();
+		let err = config.apply(&command_line).err().unwrap();
+		assert_eq!(err.source, "command line");
+	}
+
+	#[test]
+	fn bad_command_lines_are_errors()
+	{
+		let err = CommandLine::parse(&args(&["--colour"])).err().unwrap();
+		assert!(err.message.contains("unknown option '--colour'"));
+		let err = CommandLine::parse(&args(&["--width"])).err().unwrap();
+		assert!(err.message.contains("--width needs a value"));
+		assert!(CommandLine::parse(&args(&["-h"])).unwrap().help);
+	}
+}
@@ -0,0 +1,613 @@
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{BlendMode, Renderer, Texture};
+use random::XorShift;
+
+// Speckles per frame at full noise, and how much each covers the pixel under it.
+const NOISE_SPECKLES: usize = 4096;
+const NOISE_OPACITY: f32 = 0.125;
+
+fn noise_speckles(amount: f32) -> usize
+{
+	(NOISE_SPECKLES as f32 * amount.max(0.0).min(1.0)) as usize
+}
+
+/////////////////////////////////////////////////////////////////////
+// CrtSettings
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CrtSettings
+{
+	// Keep a fading copy of previous frames.
+	pub persistence: bool,
+	// Fraction of previous frame kept each frame.
+	pub persistence_amount: f32,
+	// Glow around bright lines.
+	pub bloom: bool,
+	pub bloom_strength: f32,
+	// Luminance below which pixels don't glow, from 0 to 1.
+	pub bloom_threshold: f32,
+	// Blur radius in pixels.
+	pub bloom_radius: usize,
+	// Barrel distortion of the tube.
+	pub curvature: bool,
+	pub curvature_amount: f32,
+	// Darkened lines across the screen.
+	pub scanlines: bool,
+	// How much scanlines darken, from 0 to 1.
+	pub scanline_strength: f32,
+	// Spacing between scanlines in pixels.
+	pub scanline_spacing: usize,
+	// Random speckle over the screen.
+	pub noise: bool,
+	pub noise_color: (u8, u8, u8),
+	// Amount of speckle, from 0 to 1.
+	pub noise_amount: f32,
+}
+
+impl CrtSettings
+{
+	pub fn default() -> CrtSettings
+	{
+		CrtSettings
+		{
+			persistence: true,
+			persistence_amount: 0.92,
+			bloom: false,
+			bloom_strength: 0.6,
+			bloom_threshold: 0.3,
+			bloom_radius: 4,
+			curvature: false,
+			curvature_amount: 0.08,
+			scanlines: true,
+			scanline_strength: 0.125,
+			scanline_spacing: 3,
+			noise: true,
+			noise_color: (0, 255, 0),
+			noise_amount: 1.0,
+		}
+	}
+
+	pub fn any(&self) -> bool
+	{
+		self.persistence || self.bloom || self.curvature || self.scanlines || self.noise
+	}
+
+	// Bloom and curvature can't be done with plain renderer draws.
+	pub fn needs_cpu(&self) -> bool
+	{
+		self.bloom || self.curvature
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// CrtProcessor
+// Applies CRT effects to the rendered scene on the CPU. Pixels are 4 bytes
+// in blue, green, red, alpha order, as ARGB8888 read back on a little-endian
+// machine.
+pub struct CrtProcessor
+{
+	width: usize,
+	height: usize,
+	// Last output, for phosphor persistence.
+	history: Vec<u8>,
+	// Bright pass and blur scratch, 3 channels per pixel.
+	bright: Vec<f32>,
+	blur: Vec<f32>,
+	// Copy of the frame read from when distorting.
+	source: Vec<u8>,
+	// Source pixel for each output pixel, or -1 for outside the tube.
+	distort_map: Vec<i32>,
+	// Curvature distort_map was built for.
+	distort_amount: f32,
+	// Random state for noise.
+	rng: XorShift,
+}
+
+impl CrtProcessor
+{
+	pub fn new(in_width: usize, in_height: usize) -> CrtProcessor
+	{
+		let num_pixels = in_width * in_height;
+		CrtProcessor
+		{
+			width: in_width,
+			height: in_height,
+			history: vec![0; num_pixels * 4],
+			bright: vec![0.0; num_pixels * 3],
+			blur: vec![0.0; num_pixels * 3],
+			source: vec![0; num_pixels * 4],
+			distort_map: Vec::new(),
+			distort_amount: 0.0,
+			rng: XorShift::new(0x2545f491),
+		}
+	}
+
+	pub fn process(&mut self, settings: &CrtSettings, pixels: &mut [u8])
+	{
+		if self.width == 0 || self.height == 0 || pixels.len() < self.width * self.height * 4
+		{
+			return;
+		}
+
+		if settings.noise
+		{
+			self.apply_noise(settings.noise_color, settings.noise_amount, pixels);
+		}
+		if settings.persistence
+		{
+			self.apply_persistence(settings.persistence_amount, pixels);
+		}
+		if settings.bloom
+		{
+			self.apply_bloom(settings, pixels);
+		}
+		if settings.scanlines
+		{
+			self.apply_scanlines(settings.scanline_strength, settings.scanline_spacing, pixels);
+		}
+		if settings.curvature
+		{
+			self.apply_curvature(settings.curvature_amount, pixels);
+		}
+	}
+
+	// Blend color into random pixels. Speckles are part of the frame, so they linger with persistence.
+	fn apply_noise(&mut self, color: (u8, u8, u8), amount: f32, pixels: &mut [u8])
+	{
+		let num_pixels = self.width * self.height;
+		let color = [color.2, color.1, color.0];
+		for _ in 0..noise_speckles(amount)
+		{
+			let idx = self.rng.next_u32() as usize % num_pixels;
+			for channel in 0..3
+			{
+				let byte = idx * 4 + channel;
+				let value = pixels[byte] as f32 + (color[channel] as f32 - pixels[byte] as f32) * NOISE_OPACITY;
+				pixels[byte] = value as u8;
+			}
+		}
+	}
+
+	// Phosphor fades rather than switching off, so keep the brighter of new and decayed old.
+	fn apply_persistence(&mut self, amount: f32, pixels: &mut [u8])
+	{
+		for idx in 0..(self.width * self.height)
+		{
+			for channel in 0..3
+			{
+				let byte = idx * 4 + channel;
+				let decayed = (self.history[byte] as f32 * amount) as u8;
+				pixels[byte] = pixels[byte].max(decayed);
+				self.history[byte] = pixels[byte];
+			}
+		}
+	}
+
+	fn apply_bloom(&mut self, settings: &CrtSettings, pixels: &mut [u8])
+	{
+		let num_pixels = self.width * self.height;
+		let threshold = settings.bloom_threshold * 255.0;
+
+		// Bright pass.
+		for idx in 0..num_pixels
+		{
+			let luminance = pixels[idx * 4] as f32 * 0.114 + pixels[idx * 4 + 1] as f32 * 0.587 + pixels[idx * 4 + 2] as f32 * 0.299;
+			let keep = if luminance > threshold { 1.0 } else { 0.0 };
+			for channel in 0..3
+			{
+				self.bright[idx * 3 + channel] = pixels[idx * 4 + channel] as f32 * keep;
+			}
+		}
+
+		// Separable box blur, bright -> blur -> bright.
+		let radius = settings.bloom_radius.max(1);
+		box_blur(&self.bright, &mut self.blur, self.width, self.height, radius, 3, self.width * 3);
+		box_blur(&self.blur, &mut self.bright, self.height, self.width, radius, self.width * 3, 3);
+
+		for idx in 0..num_pixels
+		{
+			for channel in 0..3
+			{
+				let byte = idx * 4 + channel;
+				let value = pixels[byte] as f32 + self.bright[idx * 3 + channel] * settings.bloom_strength;
+				pixels[byte] = value.min(255.0) as u8;
+			}
+		}
+	}
+
+	fn apply_scanlines(&mut self, strength: f32, spacing: usize, pixels: &mut [u8])
+	{
+		let scale = 1.0 - strength.max(0.0).min(1.0);
+		let mut y = 0;
+		while y < self.height
+		{
+			let row = y * self.width * 4;
+			for x in 0..self.width
+			{
+				for channel in 0..3
+				{
+					let byte = row + x * 4 + channel;
+					pixels[byte] = (pixels[byte] as f32 * scale) as u8;
+				}
+			}
+			y += spacing.max(1);
+		}
+	}
+
+	fn apply_curvature(&mut self, amount: f32, pixels: &mut [u8])
+	{
+		if self.distort_map.is_empty() || self.distort_amount != amount
+		{
+			self.build_distort_map(amount);
+		}
+
+		let num_bytes = self.source.len();
+		self.source.copy_from_slice(&pixels[..num_bytes]);
+		for idx in 0..(self.width * self.height)
+		{
+			let source_idx = self.distort_map[idx];
+			for channel in 0..3
+			{
+				pixels[idx * 4 + channel] = if source_idx < 0 { 0 } else { self.source[source_idx as usize * 4 + channel] };
+			}
+		}
+	}
+
+	fn build_distort_map(&mut self, amount: f32)
+	{
+		self.distort_map.clear();
+		self.distort_amount = amount;
+		let half_width = self.width as f32 * 0.5;
+		let half_height = self.height as f32 * 0.5;
+		for y in 0..self.height
+		{
+			for x in 0..self.width
+			{
+				let u = (x as f32 + 0.5) / half_width - 1.0;
+				let v = (y as f32 + 0.5) / half_height - 1.0;
+				let factor = 1.0 + amount * (u * u + v * v);
+				let source_u = u * factor;
+				let source_v = v * factor;
+				if source_u.abs() > 1.0 || source_v.abs() > 1.0
+				{
+					self.distort_map.push(-1);
+					continue;
+				}
+
+				let source_x = (((source_u + 1.0) * half_width) as usize).min(self.width - 1);
+				let source_y = (((source_v + 1.0) * half_height) as usize).min(self.height - 1);
+				self.distort_map.push((source_y * self.width + source_x) as i32);
+			}
+		}
+	}
+}
+
+// Box blur along lines of length elements, each element stride apart and
+// each line line_stride apart, for 3 interleaved channels.
+fn box_blur(input: &[f32], output: &mut [f32], length: usize, num_lines: usize, radius: usize, stride: usize, line_stride: usize)
+{
+	let scale = 1.0 / (radius * 2 + 1) as f32;
+	for line in 0..num_lines
+	{
+		let base = line * line_stride;
+		for channel in 0..3
+		{
+			let at = |idx: usize| input[base + idx * stride + channel];
+			let mut sum = 0.0;
+			for idx in 0..(radius + 1).min(length)
+			{
+				sum += at(idx);
+			}
+
+			for idx in 0..length
+			{
+				output[base + idx * stride + channel] = sum * scale;
+				if idx + radius + 1 < length
+				{
+					sum += at(idx + radius + 1);
+				}
+				if idx >= radius
+				{
+					sum -= at(idx - radius);
+				}
+			}
+		}
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// CrtStage
+// Post-process stage the scene is rendered through. The scene is drawn into a
+// target texture that keeps the last frame for persistence, and noise and
+// scanlines are drawn over it by the renderer. Bloom and curvature, and any
+// effect on the software renderer, read the frame back for CrtProcessor.
+pub struct CrtStage
+{
+	processor: CrtProcessor,
+	width: u32,
+	height: u32,
+	// Texture the scene is drawn into, or None to draw straight to the window.
+	scene: Option<Texture>,
+	// Frame processed on the CPU, on its way to the window.
+	output: Option<Texture>,
+	// Software rendering reads back cheaply, so always process on the CPU.
+	software: bool,
+	// Whether this frame is processed on the CPU.
+	use_cpu: bool,
+	// Whether this frame is being drawn into scene.
+	in_scene: bool,
+	// Colour the frame was cleared to, which scanlines fade towards.
+	background: Color,
+}
+
+impl CrtStage
+{
+	pub fn new(renderer: &Renderer, in_width: u32, in_height: u32) -> CrtStage
+	{
+		CrtStage
+		{
+			processor: CrtProcessor::new(in_width as usize, in_height as usize),
+			width: in_width,
+			height: in_height,
+			scene: renderer.create_texture_target(PixelFormatEnum::ARGB8888, in_width, in_height).ok(),
+			output: renderer.create_texture_streaming(PixelFormatEnum::ARGB8888, in_width, in_height).ok(),
+			software: renderer.info().name == "software",
+			use_cpu: false,
+			in_scene: false,
+			background: Color::RGB(0, 0, 0),
+		}
+	}
+
+	// Start drawing a frame over background. Without render targets the frame
+	// is drawn to the window, and effects are only possible on the CPU.
+	pub fn begin_frame(&mut self, renderer: &mut Renderer, settings: &CrtSettings, background: Color) -> Result<(), String>
+	{
+		self.in_scene = false;
+		self.background = background;
+		if let Some(texture) = self.scene.take()
+		{
+			if let Some(mut target) = renderer.render_target()
+			{
+				try!(target.set(texture).map_err(|err| self.disable(err)));
+				self.in_scene = true;
+			}
+		}
+		self.use_cpu = settings.any() && (self.software || !self.in_scene || settings.needs_cpu()) && self.output.is_some();
+
+		if settings.persistence && !self.use_cpu && self.in_scene
+		{
+			let (r, g, b) = background.rgb();
+			let alpha = ((1.0 - settings.persistence_amount) * 255.0).max(1.0) as u8;
+			renderer.set_draw_color(Color::RGBA(r, g, b, alpha));
+			renderer.set_blend_mode(BlendMode::Blend);
+			try!(renderer.fill_rect(Rect::new(0, 0, self.width, self.height)).map_err(|err| self.disable(err)));
+		}
+		else
+		{
+			renderer.set_draw_color(background);
+			renderer.clear();
+		}
+
+		if settings.noise && !self.use_cpu && self.in_scene
+		{
+			let speckles = noise_speckles(settings.noise_amount);
+			let mut points = Vec::with_capacity(speckles);
+			for _ in 0..speckles
+			{
+				let x = self.processor.rng.next_u32() % self.width;
+				let y = self.processor.rng.next_u32() % self.height;
+				points.push(Point::new(x as i32, y as i32));
+			}
+			let (r, g, b) = settings.noise_color;
+			renderer.set_draw_color(Color::RGBA(r, g, b, (NOISE_OPACITY * 255.0) as u8));
+			renderer.set_blend_mode(BlendMode::Blend);
+			try!(renderer.draw_points(&points).map_err(|err| self.disable(err)));
+		}
+		Ok(())
+	}
+
+	// Show the frame drawn since begin_frame on the window, with effects.
+	pub fn end_frame(&mut self, renderer: &mut Renderer, settings: &CrtSettings) -> Result<(), String>
+	{
+		// Read back before leaving the scene, as reads come from the current target.
+		let pixels = if self.use_cpu { Some(try!(renderer.read_pixels(None, PixelFormatEnum::ARGB8888).map_err(|err| self.disable(err)))) } else { None };
+		if self.in_scene
+		{
+			if let Some(mut target) = renderer.render_target()
+			{
+				self.scene = try!(target.reset().map_err(|err| self.disable(err)));
+			}
+		}
+
+		if let (Some(mut pixels), Some(output)) = (pixels, self.output.as_mut())
+		{
+			self.processor.process(settings, &mut pixels);
+			try!(output.update(None, &pixels, self.width as usize * 4).map_err(|err| format!("{:?}", err)));
+			renderer.copy(output, None, None);
+			return Ok(());
+		}
+
+		if let Some(ref scene) = self.scene
+		{
+			renderer.copy(scene, None, None);
+		}
+		if settings.scanlines
+		{
+			let spacing = settings.scanline_spacing.max(1);
+			let rects: Vec<Rect> = (0..self.height as usize).filter(|y| y % spacing == 0).map(|y| Rect::new(0, y as i32, self.width, 1)).collect();
+			let (r, g, b) = self.background.rgb();
+			renderer.set_draw_color(Color::RGBA(r, g, b, (settings.scanline_strength.max(0.0).min(1.0) * 255.0) as u8));
+			renderer.set_blend_mode(BlendMode::Blend);
+			try!(renderer.fill_rects(&rects));
+		}
+		Ok(())
+	}
+
+	// Give up on effects after a renderer error, drawing straight to the window from now on.
+	fn disable(&mut self, err: String) -> String
+	{
+		self.scene = None;
+		self.output = None;
+		err
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	const WIDTH: usize = 32;
+	const HEIGHT: usize = 24;
+
+	// Settings with every effect off, for turning on one at a time.
+	fn no_effects() -> CrtSettings
+	{
+		CrtSettings
+		{
+			persistence: false,
+			bloom: false,
+			curvature: false,
+			scanlines: false,
+			noise: false,
+			..CrtSettings::default()
+		}
+	}
+
+	fn black_frame() -> Vec<u8>
+	{
+		let mut pixels = vec![0; WIDTH * HEIGHT * 4];
+		for idx in 0..(WIDTH * HEIGHT)
+		{
+			pixels[idx * 4 + 3] = 255;
+		}
+		return pixels;
+	}
+
+	fn set_pixel(pixels: &mut [u8], x: usize, y: usize, value: u8)
+	{
+		let idx = (y * WIDTH + x) * 4;
+		for channel in 0..3
+		{
+			pixels[idx + channel] = value;
+		}
+	}
+
+	fn green(pixels: &[u8], x: usize, y: usize) -> u8
+	{
+		pixels[(y * WIDTH + x) * 4 + 1]
+	}
+
+	#[test]
+	fn no_effects_leave_the_frame_alone()
+	{
+		let mut crt = CrtProcessor::new(WIDTH, HEIGHT);
+		let mut pixels = black_frame();
+		set_pixel(&mut pixels, 5, 7, 200);
+		let original = pixels.clone();
+		crt.process(&no_effects(), &mut pixels);
+		assert_eq!(pixels, original);
+	}
+
+	#[test]
+	fn bloom_spreads_bright_pixels_only()
+	{
+		let settings = CrtSettings { bloom: true, ..no_effects() };
+		let mut crt = CrtProcessor::new(WIDTH, HEIGHT);
+		let mut pixels = black_frame();
+		set_pixel(&mut pixels, 16, 12, 255);
+		// Below the bloom threshold.
+		set_pixel(&mut pixels, 4, 4, 40);
+		crt.process(&settings, &mut pixels);
+
+		assert_eq!(green(&pixels, 16, 12), 255);
+		// Glow within the blur radius on both axes, fading with distance.
+		assert!(green(&pixels, 17, 12) > 0);
+		assert!(green(&pixels, 16, 14) > 0);
+		assert!(green(&pixels, 18, 14) > 0);
+		assert!(green(&pixels, 16 + settings.bloom_radius + 1, 12) == 0);
+		// Dim pixels don't glow.
+		assert_eq!(green(&pixels, 4, 4), 40);
+		assert_eq!(green(&pixels, 5, 4), 0);
+		// Alpha is untouched.
+		assert!(pixels.chunks(4).all(|pixel| pixel[3] == 255));
+	}
+
+	#[test]
+	fn curvature_keeps_the_centre_and_blanks_the_corners()
+	{
+		let settings = CrtSettings { curvature: true, curvature_amount: 0.2, ..no_effects() };
+		let mut crt = CrtProcessor::new(WIDTH, HEIGHT);
+		let mut pixels = black_frame();
+		for y in 0..HEIGHT
+		{
+			for x in 0..WIDTH
+			{
+				set_pixel(&mut pixels, x, y, 100 + x as u8);
+			}
+		}
+		crt.process(&settings, &mut pixels);
+
+		// Middle of the tube barely moves.
+		let centre = green(&pixels, WIDTH / 2, HEIGHT / 2);
+		assert!((centre as i32 - (100 + WIDTH / 2) as i32).abs() <= 1);
+		// Corners fall outside the tube.
+		assert_eq!(green(&pixels, 0, 0), 0);
+		assert_eq!(green(&pixels, WIDTH - 1, HEIGHT - 1), 0);
+		// Near the edges, pixels are pulled from further out.
+		let y = HEIGHT / 2;
+		assert!(green(&pixels, 2, y) < 102);
+		assert!(green(&pixels, WIDTH - 3, y) > 100 + (WIDTH - 3) as u8);
+	}
+
+	#[test]
+	fn scanlines_darken_every_nth_row()
+	{
+		let settings = CrtSettings { scanlines: true, scanline_strength: 0.5, scanline_spacing: 3, ..no_effects() };
+		let mut crt = CrtProcessor::new(WIDTH, HEIGHT);
+		let mut pixels = black_frame();
+		for y in 0..HEIGHT
+		{
+			set_pixel(&mut pixels, 3, y, 200);
+		}
+		crt.process(&settings, &mut pixels);
+
+		for y in 0..HEIGHT
+		{
+			let expected = if y % 3 == 0 { 100 } else { 200 };
+			assert_eq!(green(&pixels, 3, y), expected);
+		}
+	}
+
+	#[test]
+	fn persistence_fades_previous_frames()
+	{
+		let settings = CrtSettings { persistence: true, persistence_amount: 0.5, ..no_effects() };
+		let mut crt = CrtProcessor::new(WIDTH, HEIGHT);
+		let mut pixels = black_frame();
+		set_pixel(&mut pixels, 10, 10, 200);
+		crt.process(&settings, &mut pixels);
+		assert_eq!(green(&pixels, 10, 10), 200);
+
+		let mut pixels = black_frame();
+		crt.process(&settings, &mut pixels);
+		assert_eq!(green(&pixels, 10, 10), 100);
+
+		let mut pixels = black_frame();
+		crt.process(&settings, &mut pixels);
+		assert_eq!(green(&pixels, 10, 10), 50);
+	}
+
+	#[test]
+	fn noise_speckles_in_its_colour()
+	{
+		let settings = CrtSettings { noise: true, noise_color: (0, 255, 0), noise_amount: 0.1, ..no_effects() };
+		let mut crt = CrtProcessor::new(WIDTH, HEIGHT);
+		let mut pixels = black_frame();
+		crt.process(&settings, &mut pixels);
+
+		let speckled = pixels.chunks(4).filter(|pixel| pixel[1] > 0).count();
+		assert!(speckled > 0);
+		assert!(pixels.chunks(4).all(|pixel| pixel[0] == 0 && pixel[2] == 0));
+	}
+}
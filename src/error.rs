@@ -0,0 +1,47 @@
+use std::fmt;
+use config::ConfigError;
+
+/////////////////////////////////////////////////////////////////////
+// GameError
+// Errors that stop the game starting. Missing audio, or a missing
+// accelerated renderer, are worked around rather than reported here.
+#[derive(Debug)]
+pub enum GameError
+{
+	// Bad command line or config file.
+	Config(ConfigError),
+	// SDL itself failed to start.
+	Init(String),
+	// No video subsystem, such as running without a display.
+	Video(String),
+	// Window couldn't be created at the requested size.
+	Window(u32, u32, String),
+	// Neither an accelerated nor a software renderer could be created.
+	Renderer(String),
+	// Input events can't be read.
+	Events(String),
+}
+
+impl fmt::Display for GameError
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match *self
+		{
+			GameError::Config(ref err) => write!(f, "{}", err),
+			GameError::Init(ref err) => write!(f, "Failed to start SDL: {}. Check SDL2 is installed, or that SDL2.dll is next to the game.", err),
+			GameError::Video(ref err) => write!(f, "Failed to start video: {}. A display is needed to play.", err),
+			GameError::Window(width, height, ref err) => write!(f, "Failed to create a {}x{} window: {}. Try a smaller --width and --height, or --windowed.", width, height, err),
+			GameError::Renderer(ref err) => write!(f, "Failed to create a renderer: {}. Try updating your graphics drivers.", err),
+			GameError::Events(ref err) => write!(f, "Failed to read input events: {}.", err),
+		}
+	}
+}
+
+impl From<ConfigError> for GameError
+{
+	fn from(err: ConfigError) -> GameError
+	{
+		GameError::Config(err)
+	}
+}
@@ -0,0 +1,220 @@
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::fs;
+use std::fs::File;
+use std::path::Path;
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+use sdl2::pixels::Color;
+use time;
+use audio::write_wav;
+use math::Vec2d;
+use mixer::{Bus, Mixer, MixerChannel, NUM_BUSES};
+use scope::ScopeBuffer;
+use theme::Theme;
+
+pub const EXPORT_DIRECTORY: &'static str = "exports";
+// Seconds of target tone rendered, two notes of its envelope.
+const EXPORT_DURATION: f32 = 2.0;
+
+/////////////////////////////////////////////////////////////////////
+// RoundExport
+// What's needed to review a round away from the game.
+pub struct RoundExport
+{
+	pub level: u32,
+	// Seed the round was generated from, and the game's seed if one was set.
+	pub round_seed: u32,
+	pub game_seed: Option<u32>,
+	pub target_idx: usize,
+	pub candidates: Vec<Candidate>,
+}
+
+pub struct Candidate
+{
+	pub channels: [MixerChannel; 3],
+	// Stereo position of each channel.
+	pub pans: [f32; 3],
+	// Outline as drawn, in screen coordinates.
+	pub outline: Vec<Vec2d>,
+}
+
+// Name of an export, from the local time and the round's seed.
+fn export_name(round: &RoundExport) -> io::Result<String>
+{
+	let now = time::now();
+	let stamp = try!(now.strftime("%Y%m%d-%H%M%S").map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string())));
+	Ok(format!("round-{}-{}", stamp, round.round_seed))
+}
+
+// Path in directory for name, numbered if an export already has it, as
+// names only change once a second.
+fn unused_path(directory: &str, name: &str) -> String
+{
+	let mut path = format!("{}/{}", directory, name);
+	let mut number = 2;
+	while Path::new(&format!("{}.wav", path)).exists()
+	{
+		path = format!("{}/{}-{}", directory, name, number);
+		number += 1;
+	}
+	return path;
+}
+
+// Write .wav, .svg and .json files for round into the export directory,
+// returning the path they share without an extension.
+pub fn export_round(round: &RoundExport, sample_rate: i32, screen_size: Vec2d, theme: &Theme) -> io::Result<String>
+{
+	try!(fs::create_dir_all(EXPORT_DIRECTORY));
+	let path = unused_path(EXPORT_DIRECTORY, &try!(export_name(round)));
+
+	let target = &round.candidates[round.target_idx];
+	let samples = render_tone(target, sample_rate);
+	let mut wav_file = BufWriter::new(try!(File::create(format!("{}.wav", path))));
+	try!(write_wav(&mut wav_file, sample_rate as u32, 2, &samples));
+
+	let mut svg_file = BufWriter::new(try!(File::create(format!("{}.svg", path))));
+	try!(write_svg(&mut svg_file, round, screen_size, theme));
+
+	let mut json_file = BufWriter::new(try!(File::create(format!("{}.json", path))));
+	try!(write_json(&mut json_file, round));
+
+	return Ok(path);
+}
+
+// Run the target through a mixer of its own, with only the tone bus up.
+fn render_tone(target: &Candidate, sample_rate: i32) -> Vec<f32>
+{
+	let (tx, rx) = channel();
+	let scope = Arc::new(Mutex::new(ScopeBuffer::new(1, sample_rate as f32)));
+	let mut bus_volumes = [0.0; NUM_BUSES];
+	bus_volumes[Bus::Master as usize] = 1.0;
+	bus_volumes[Bus::Tone as usize] = 1.0;
+	let mut mixer = Mixer::new(sample_rate, rx, scope, bus_volumes);
+
+	// The mixer keeps rx until it's done, so these sends can't fail.
+	for idx in 0..target.channels.len()
+	{
+		let _ = tx.send(target.channels[idx]);
+		let _ = tx.send(MixerChannel::Pan(idx, target.pans[idx]));
+	}
+	let _ = tx.send(MixerChannel::Trigger);
+	// A fresh mixer would glide up from silence, so start on the tone.
+	mixer.settle();
+
+	let mut samples = vec![0.0; (EXPORT_DURATION * sample_rate as f32) as usize * 2];
+	mixer.mix(&mut samples);
+	return samples;
+}
+
+fn hex_color(color: Color) -> String
+{
+	let (r, g, b) = color.rgb();
+	format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn write_svg<W: Write>(out: &mut W, round: &RoundExport, screen_size: Vec2d, theme: &Theme) -> io::Result<()>
+{
+	try!(writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">", screen_size.x, screen_size.y, screen_size.x, screen_size.y));
+	try!(writeln!(out, "  <rect width=\"100%\" height=\"100%\" fill=\"{}\"/>", hex_color(theme.background)));
+	for (idx, candidate) in round.candidates.iter().enumerate()
+	{
+		let is_target = idx == round.target_idx;
+		let color = if is_target { theme.shape_correct } else { theme.shape };
+		try!(write!(out, "  <polyline id=\"{}{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"", if is_target { "target-" } else { "distractor-" }, idx, hex_color(color)));
+		// Repeat the first point to close the outline.
+		for point in candidate.outline.iter().chain(candidate.outline.first())
+		{
+			try!(write!(out, "{:.2},{:.2} ", point.x, point.y));
+		}
+		try!(writeln!(out, "\"/>"));
+	}
+	writeln!(out, "</svg>")
+}
+
+fn write_channels_json<W: Write>(out: &mut W, candidate: &Candidate) -> io::Result<()>
+{
+	try!(write!(out, "["));
+	for idx in 0..candidate.channels.len()
+	{
+		let (wave, freq, volume) = match candidate.channels[idx]
+		{
+			MixerChannel::Sine(f, v) => ("sine", f, v),
+			MixerChannel::Square(f, v) => ("square", f, v),
+			MixerChannel::Sawtooth(f, v) => ("sawtooth", f, v),
+			_ => ("none", 0.0, 0.0),
+		};
+		try!(write!(out, "{}{{ \"wave\": \"{}\", \"freq\": {}, \"volume\": {}, \"pan\": {} }}", if idx > 0 { ", " } else { "" }, wave, freq, volume, candidate.pans[idx]));
+	}
+	write!(out, "]")
+}
+
+fn write_json<W: Write>(out: &mut W, round: &RoundExport) -> io::Result<()>
+{
+	try!(writeln!(out, "{{"));
+	try!(writeln!(out, "  \"level\": {},", round.level));
+	try!(writeln!(out, "  \"round_seed\": {},", round.round_seed));
+	match round.game_seed
+	{
+		Some(seed) => try!(writeln!(out, "  \"game_seed\": {},", seed)),
+		None => try!(writeln!(out, "  \"game_seed\": null,")),
+	}
+	try!(writeln!(out, "  \"target\": {},", round.target_idx));
+	try!(writeln!(out, "  \"candidates\": ["));
+	for (idx, candidate) in round.candidates.iter().enumerate()
+	{
+		try!(write!(out, "    "));
+		try!(write_channels_json(out, candidate));
+		try!(writeln!(out, "{}", if idx + 1 < round.candidates.len() { "," } else { "" }));
+	}
+	try!(writeln!(out, "  ]"));
+	writeln!(out, "}}")
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::env;
+	use std::fs;
+	use std::fs::File;
+	use std::process;
+	use super::*;
+
+	#[test]
+	fn tone_starts_at_its_pitch()
+	{
+		let candidate = Candidate
+		{
+			channels: [MixerChannel::Sine(440.0, 1.0), MixerChannel::Square(0.0, 0.0), MixerChannel::Sawtooth(0.0, 0.0)],
+			pans: [0.0; 3],
+			outline: Vec::new(),
+		};
+		let samples = render_tone(&candidate, 44100);
+		assert_eq!(samples.len(), (EXPORT_DURATION * 44100.0) as usize * 2);
+
+		// A tone gliding up from silence would cross zero far less often
+		// than the two crossings a cycle of 440 Hz makes.
+		let left: Vec<f32> = samples[..882 * 2].iter().step_by(2).cloned().filter(|&sample| sample != 0.0).collect();
+		let crossings = left.windows(2).filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0)).count();
+		assert!(crossings >= 16 && crossings <= 18, "{} crossings in 20ms", crossings);
+	}
+
+	#[test]
+	fn exports_in_the_same_second_are_numbered()
+	{
+		let directory = env::temp_dir().join(format!("oscillomatch-export-test-{}", process::id()));
+		fs::create_dir_all(&directory).unwrap();
+		let directory = directory.to_str().unwrap().to_string();
+
+		let first = unused_path(&directory, "round");
+		assert_eq!(first, format!("{}/round", directory));
+		File::create(format!("{}.wav", first)).unwrap();
+		let second = unused_path(&directory, "round");
+		assert_eq!(second, format!("{}/round-2", directory));
+		File::create(format!("{}.wav", second)).unwrap();
+		assert_eq!(unused_path(&directory, "round"), format!("{}/round-3", directory));
+
+		fs::remove_dir_all(&directory).unwrap();
+	}
+}
@@ -0,0 +1,105 @@
+use sdl2::keyboard::Keycode;
+
+/////////////////////////////////////////////////////////////////////
+// Action
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Action
+{
+	// Play the target sound again.
+	Replay,
+	// Toggle auditioning shapes on hover.
+	Preview,
+	// Toggle the scope hint trace.
+	Hint,
+	// Open or close the options menu.
+	Options,
+	// Save the round's target tone and shapes for review.
+	Export,
+	// Open or close the achievements screen.
+	Achievements,
+}
+
+pub const ACTIONS: [Action; 6] = [Action::Replay, Action::Preview, Action::Hint, Action::Options, Action::Export, Action::Achievements];
+
+impl Action
+{
+	// Name used in the config file.
+	pub fn name(&self) -> &'static str
+	{
+		match *self
+		{
+			Action::Replay => "replay",
+			Action::Preview => "preview",
+			Action::Hint => "hint",
+			Action::Options => "options",
+			Action::Export => "export",
+			Action::Achievements => "achievements",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Action>
+	{
+		ACTIONS.iter().find(|action| action.name() == name).map(|action| *action)
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// Bindings
+#[derive(Copy, Clone)]
+pub struct Bindings
+{
+	// Key for each action, indexed like ACTIONS.
+	keys: [Keycode; 6],
+}
+
+impl Bindings
+{
+	pub fn default() -> Bindings
+	{
+		Bindings
+		{
+			keys: [Keycode::R, Keycode::P, Keycode::H, Keycode::Escape, Keycode::E, Keycode::A],
+		}
+	}
+
+	pub fn key(&self, action: Action) -> Keycode
+	{
+		self.keys[action as usize]
+	}
+
+	// Bind key to action, swapping with any action already using it.
+	pub fn set(&mut self, action: Action, key: Keycode)
+	{
+		let old_key = self.keys[action as usize];
+		for idx in 0..self.keys.len()
+		{
+			if self.keys[idx] == key
+			{
+				self.keys[idx] = old_key;
+			}
+		}
+		self.keys[action as usize] = key;
+	}
+
+	pub fn action(&self, key: Keycode) -> Option<Action>
+	{
+		self.keys.iter().position(|&bound| bound == key).map(|idx| ACTIONS[idx])
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// InputSource
+// Devices other than the keyboard and mouse, polled after SDL's events
+// each frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InputEvent
+{
+	// Answer with the shape at this index.
+	Pick(usize),
+}
+
+pub trait InputSource
+{
+	// Add events since the last poll to events.
+	fn poll(&mut self, events: &mut Vec<InputEvent>);
+}
@@ -0,0 +1,154 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Point;
+use sdl2::render::{Renderer, BlendMode};
+use math::*;
+use theme::color_components;
+
+// Number of alpha levels coverage is quantised to when flushing.
+const NUM_ALPHA_LEVELS: usize = 16;
+
+/////////////////////////////////////////////////////////////////////
+// LineBatch
+// Collects thick anti-aliased lines and draws them together. Each line is
+// rasterised as a capsule, so consecutive segments get round joins, and
+// overlapping coverage keeps the maximum so joins don't darken.
+pub struct LineBatch
+{
+	width: usize,
+	height: usize,
+	// Line width in pixels.
+	pub thickness: f32,
+	// Coverage per screen pixel, from 0 to 255.
+	coverage: Vec<u8>,
+	// Pixels with non-zero coverage.
+	touched: Vec<usize>,
+	// Points to draw for each alpha level.
+	buckets: Vec<Vec<Point>>,
+	// First draw error, until taken. Lines are flushed from deep in shape and
+	// text drawing, so errors are collected here and reported once.
+	error: Option<String>,
+	error_taken: bool,
+}
+
+impl LineBatch
+{
+	pub fn new(in_width: usize, in_height: usize, in_thickness: f32) -> LineBatch
+	{
+		LineBatch
+		{
+			width: in_width,
+			height: in_height,
+			thickness: in_thickness,
+			coverage: vec![0; in_width * in_height],
+			touched: Vec::new(),
+			buckets: vec![Vec::new(); NUM_ALPHA_LEVELS],
+			error: None,
+			error_taken: false,
+		}
+	}
+
+	pub fn add_line(&mut self, a: Vec2d, b: Vec2d)
+	{
+		let radius = self.thickness.max(1.0) * 0.5;
+		let extent = radius + 1.0;
+		let min_x = (a.x.min(b.x) - extent).max(0.0) as usize;
+		let min_y = (a.y.min(b.y) - extent).max(0.0) as usize;
+		let max_x = (a.x.max(b.x) + extent).max(0.0).min(self.width as f32) as usize;
+		let max_y = (a.y.max(b.y) + extent).max(0.0).min(self.height as f32) as usize;
+
+		let ab = b - a;
+		let length_squared = ab.magnitude_squared();
+		for y in min_y..max_y
+		{
+			for x in min_x..max_x
+			{
+				// Distance from pixel centre to the segment.
+				let p = Vec2d::new(x as f32 + 0.5, y as f32 + 0.5);
+				let t = if length_squared > 0.0 { ((p - a).dot(ab) / length_squared).max(0.0).min(1.0) } else { 0.0 };
+				let distance = p.distance(a + ab * t);
+
+				let amount = (radius + 0.5 - distance).max(0.0).min(1.0);
+				if amount <= 0.0
+				{
+					continue;
+				}
+
+				let idx = y * self.width + x;
+				let value = (amount * 255.0) as u8;
+				if self.coverage[idx] == 0
+				{
+					self.touched.push(idx);
+				}
+				if value > self.coverage[idx]
+				{
+					self.coverage[idx] = value;
+				}
+			}
+		}
+	}
+
+	// Add a closed outline of points transformed by transform.
+	pub fn add_loop(&mut self, points: &[Vec2d], transform: &Mat3)
+	{
+		let num_points = points.len();
+		if num_points < 2
+		{
+			return;
+		}
+
+		let mut last = transform.transform_point(points[num_points - 1]);
+		for idx in 0..num_points
+		{
+			let next = transform.transform_point(points[idx]);
+			self.add_line(last, next);
+			last = next;
+		}
+	}
+
+	// Draw everything added since the last flush in color, then reset.
+	pub fn flush(&mut self, renderer: &mut Renderer, color: Color)
+	{
+		if self.touched.is_empty()
+		{
+			return;
+		}
+
+		for &idx in self.touched.iter()
+		{
+			let level = (self.coverage[idx] as usize * NUM_ALPHA_LEVELS) / 256;
+			self.buckets[level].push(Point::new((idx % self.width) as i32, (idx / self.width) as i32));
+			self.coverage[idx] = 0;
+		}
+		self.touched.clear();
+
+		let (r, g, b, a) = color_components(color);
+		renderer.set_blend_mode(BlendMode::Blend);
+		for level in 0..NUM_ALPHA_LEVELS
+		{
+			if self.buckets[level].is_empty()
+			{
+				continue;
+			}
+			let alpha = a as usize * (level + 1) / NUM_ALPHA_LEVELS;
+			renderer.set_draw_color(Color::RGBA(r, g, b, alpha as u8));
+			if let Err(err) = renderer.draw_points(&self.buckets[level])
+			{
+				if self.error.is_none() && !self.error_taken
+				{
+					self.error = Some(err);
+				}
+			}
+			self.buckets[level].clear();
+		}
+	}
+
+	// The first error drawing lines, returned only once.
+	pub fn take_error(&mut self) -> Option<String>
+	{
+		if self.error.is_some()
+		{
+			self.error_taken = true;
+		}
+		self.error.take()
+	}
+}
@@ -1,27 +1,94 @@
 extern crate sdl2;
 extern crate time;
 extern crate rand;
+#[cfg(feature = "midi")]
+extern crate midir;
 
-use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::audio::AudioSpecDesired;
 use sdl2::pixels::Color;
 use sdl2::event::Event;
-use sdl2::rect::{Point, Rect};
-use sdl2::render::{Renderer, BlendMode};
-
-
+use sdl2::keyboard::Keycode;
+use sdl2::rect::Point;
+use sdl2::render::Renderer;
+use sdl2::video::{FullscreenType, Window};
+use sdl2::{Sdl, VideoSubsystem};
+
+
+pub mod achievements;
+pub mod audio;
+pub mod config;
+pub mod crt;
+pub mod error;
+pub mod export;
+pub mod input;
+pub mod lines;
 pub mod math;
+#[cfg(feature = "midi")]
+pub mod midi;
+pub mod mixer;
+pub mod music;
+pub mod options;
+pub mod particles;
+pub mod pitch;
+pub mod puzzle;
+pub mod random;
+pub mod scope;
+pub mod sfx;
+pub mod theme;
+pub mod tween;
+pub mod stats;
 
 use std::io::prelude::*;
 use std::fs::File;
 use std::f32::consts::{PI};
-use std::sync::mpsc::{Sender, Receiver, channel};
+use std::sync::mpsc::{Sender, channel};
+use std::sync::{Arc, Mutex};
 use math::*;
+use achievements::{Achievements, Streak};
+use audio::{AudioBackend, BackendKind, CaptureBackend, NullBackend, SdlBackend};
+use config::{CommandLine, Config, USAGE};
+use crt::{CrtSettings, CrtStage};
+use error::GameError;
+use export::{Candidate, RoundExport};
+use input::{Action, InputEvent, InputSource};
+use options::OptionsMenu;
+use lines::LineBatch;
+use mixer::{Bus, Mixer, MixerChannel, MixerChannelParams, sine_wave, square_wave, sawtooth_wave};
+use puzzle::{Bias, Family, Sound};
+use stats::PlayerStats;
+use scope::{ScopeBuffer, SCOPE_BUFFER_SIZE, FFT_SIZE};
+use sfx::Sfx;
+use theme::{Theme, with_alpha};
+use tween::{Easing, Tween};
+use particles::{Emitter, ParticleKind, ParticleSystem};
 use time::*;
-use rand::Rng;
+use rand::{Rng, SeedableRng, StdRng};
 
-const WIDTH: i32 = 1024;
-const HEIGHT: i32 = 768;
 const SIZE: f32 = 96.0;
+const MAX_SHAPES_PER_ROW: usize = 4;
+const PREVIEW_TIME: f32 = 1.5;
+const MORPH_DURATION: f32 = 0.5;
+const FEEDBACK_DURATION: f32 = 0.75;
+const FEEDBACK_GROW: f32 = 0.3;
+const FEEDBACK_SHAKE: f32 = 12.0;
+const MAX_PARTICLES: usize = 2048;
+// Ambient particles spawned per second.
+const AMBIENT_RATE: f32 = 24.0;
+const PREVIEW_PENALTY: i32 = 2;
+// Seconds counted in before the first round's target plays.
+const COUNTDOWN_SECONDS: i32 = 3;
+// Line widths in pixels.
+const SHAPE_THICKNESS: f32 = 2.0;
+const TEXT_THICKNESS: f32 = 1.5;
+// Outline point count limits, and how densely to sample.
+const MIN_SHAPE_POINTS: usize = 64;
+const MAX_SHAPE_POINTS: usize = 1024;
+const PIXELS_PER_SEGMENT: f32 = 3.0;
+const POINTS_PER_CYCLE: f32 = 24.0;
+// Channel blend is snapped to its target once this close.
+const CHANNEL_EPSILON: f32 = 0.001;
+// Turns per second of the polar outline.
+const SPIN_RATE: f32 = 0.125;
 
 
 /////////////////////////////////////////////////////////////////////
@@ -40,165 +107,84 @@ fn draw_line(renderer: &mut Renderer, a: Vec2d, b: Vec2d)
 }
 
 /////////////////////////////////////////////////////////////////////
-// waves
-fn sine_wave(x: f32) -> f32
+// TransformStack
+struct TransformStack
 {
-	return (x * PI * 2.0).sin();
+	// Concatenated transforms, top is current.
+	stack: Vec<Mat3>,
 }
 
-fn square_wave(x: f32) -> f32
+impl TransformStack
 {
-	let mod_x = (x * 2.0) % 2.0;
-	if mod_x > 1.0
+	fn new() -> TransformStack
 	{
-		return -1.0;
+		TransformStack
+		{
+			stack: vec![Mat3::identity()],
+		}
 	}
-	return 1.0;
-}
-
-fn sawtooth_wave(x: f32) -> f32
-{
-	return (x % 1.0) * 2.0 - 1.0;
-}
-
-fn envelope(x: f32, factor: f32) -> f32
-{
-	let mod_x = x % 1.0;
-	return (factor * mod_x * (PI / (1.0 + (factor - 1.0) * mod_x))).sin();
-}
-
-/////////////////////////////////////////////////////////////////////
-// Mixer
-type MixerFunc = Fn(f32) -> f32;
-
-#[derive(Copy, Clone, Debug)]
-enum MixerChannel
-{
-	Sine(f32, f32),
-	Square(f32, f32),
-	Sawtooth(f32, f32),
-	Beep(f32),
-}
 
-#[derive(Copy, Clone)]
-struct MixerChannelParams
-{
-	phase_inc: f32,
-	phase: f32,
-	volume: f32,
-}
+	fn push(&mut self, transform: Mat3)
+	{
+		let top = self.top();
+		self.stack.push(top * transform);
+	}
 
-impl MixerChannelParams
-{
-	fn default() -> MixerChannelParams
+	fn pop(&mut self)
 	{
-		MixerChannelParams
+		if self.stack.len() > 1
 		{
-			 phase_inc: 0.0,
-			 phase: 0.0,
-			 volume: 0.0
+			self.stack.pop();
 		}
 	}
-}
-
-struct MixerCallback
-{
-	freq: f32,
-	rx: Receiver<MixerChannel>,
-	channels: [MixerChannelParams; 4],
-	channel_targets: [MixerChannelParams; 4],
 
-	time: f32,
+	fn top(&self) -> Mat3
+	{
+		self.stack[self.stack.len() - 1]
+	}
 }
 
-impl AudioCallback for MixerCallback
+/////////////////////////////////////////////////////////////////////
+// Shape
+#[derive(Copy, Clone, PartialEq)]
+enum ShapeMode
 {
-	type Channel = f32;
-	fn callback(&mut self, out: &mut [f32])
-	{
-		'running: loop
-		{
-			let result = self.rx.try_recv();
-			match result
-			{
-				Ok(channel) => 
-				{
-					match channel
-					{
-						MixerChannel::Sine(f, v) =>
-						{
-							self.channel_targets[0].phase_inc = f / self.freq;
-							self.channel_targets[0].volume = v;
-						},
-						MixerChannel::Square(f, v) =>
-						{
-							self.channel_targets[1].phase_inc = f / self.freq;
-							self.channel_targets[1].volume = v;
-						},
-						MixerChannel::Sawtooth(f, v) =>
-						{
-							self.channel_targets[2].volume = v;
-							self.channel_targets[2].phase_inc = f / self.freq;
-						},
-						MixerChannel::Beep(f) =>
-						{
-							self.channel_targets[3].volume = 8.0;
-							self.channel_targets[3].phase_inc = f / self.freq;
-							self.channel_targets[3].phase = 0.0;
-						}
-					}
-				}
-				Err(_) => break 'running,
-			}
-		}
-
-		for x in out.iter_mut()
-		{
-			let mut out_val = 0.0;
-			for idx in 0..self.channels.len()
-			{
-				self.channels[idx].phase = (self.channels[idx].phase + self.channels[idx].phase_inc) % 1.0;
-
-				let sample = match idx
-				{
-					0 => sine_wave( self.channels[idx].phase ) * envelope(self.time, 8.0),
-					1 => square_wave( self.channels[idx].phase ) * envelope(self.time, 8.0),
-					2 => sawtooth_wave( self.channels[idx].phase ) * envelope(self.time, 8.0),
-					3 =>
-					{
-						self.channel_targets[idx].volume *= 0.995;
-						sine_wave( self.channels[idx].phase )
-					},
-					_ => 0.0,
-				};
-				out_val = out_val + self.channels[idx].volume * sample;				
-
-				// Blend to target.
-				self.channels[idx].phase_inc = self.channels[idx].phase_inc * 0.999 + self.channel_targets[idx].phase_inc * 0.001;
-				self.channels[idx].volume = self.channels[idx].volume * 0.999 + self.channel_targets[idx].volume * 0.001;
-			}
-
-			*x = out_val / 4.0;
-			self.time = (self.time + 1.0 / self.freq) % 8.0;
-		}
-	}
+	// Waveform mix plotted as radius around a circle.
+	Polar,
+	// Figure traced by one waveform on x and another on y.
+	Lissajous { x_wave: usize, y_wave: usize, phase: f32 },
 }
 
-struct Mixer
+#[derive(Copy, Clone, PartialEq)]
+enum Feedback
 {
-	// Audio device.
-	device: AudioDevice<MixerCallback>
+	None,
+	// Player picked this shape and it was the target.
+	Correct,
+	// Player picked this shape and it wasn't.
+	Wrong,
+	// Target shown after a wrong pick.
+	Reveal,
 }
 
-/////////////////////////////////////////////////////////////////////
-// Shape
 type PointFunc = Fn(f32, f32) -> Vec2d;
 struct Shape
 {
-	// Position for shape.
-	position: Vec2d,
-	// Points for shape.
+	// Transform for shape.
+	transform: Transform,
+	// Radius for shape.
+	radius: f32,
+	// How shape is plotted.
+	mode: ShapeMode,
+	// Points for shape, as drawn.
 	points: Vec<Vec2d>,
+	// Cached outline before morphing, rebuilt only while channels change.
+	profile: Vec<Vec2d>,
+	profile_valid: bool,
+	// Rotation over time, kept out of the outline so it stays cached.
+	spin: f32,
+	// Spin when the morph started.
+	morph_spin: f32,
 	// Channels that have been set.
 	set_channels: [MixerChannel; 3],
 	// Channels for shape.
@@ -207,17 +193,33 @@ struct Shape
 	channel_targets: [MixerChannelParams; 3],
 	// Is selected shape?
 	is_selected: bool,
+	// Points morphed from since last reset.
+	morph_points: Vec<Vec2d>,
+	// Transform morphed from since last reset.
+	morph_transform: Transform,
+	// Morph progress from 0 to 1.
+	morph: Tween,
+	// Answer feedback being shown.
+	feedback: Feedback,
+	// Feedback progress from 0 to 1.
+	feedback_tween: Tween,
 }
 
 
 impl Shape
 {
-	fn new(in_position: Vec2d, num_points: usize, in_channels: [MixerChannel; 3] ) -> Shape 
+	fn new(in_position: Vec2d, in_channels: [MixerChannel; 3] ) -> Shape 
 	{
 		let mut shape = Shape
 		{
-			position: Vec2d::new(in_position.x, in_position.y),
-			points: Vec::with_capacity(num_points),
+			transform: Transform::new(in_position),
+			radius: SIZE,
+			mode: ShapeMode::Polar,
+			points: Vec::with_capacity(MAX_SHAPE_POINTS),
+			profile: Vec::with_capacity(MAX_SHAPE_POINTS),
+			profile_valid: false,
+			spin: 0.0,
+			morph_spin: 0.0,
 			channels:
 			[
 				MixerChannelParams::default(),
@@ -232,25 +234,97 @@ impl Shape
 			],
 			set_channels: in_channels.clone(),
 			is_selected: false,
+			morph_points: Vec::new(),
+			morph_transform: Transform::new(in_position),
+			morph: Tween::finished(1.0),
+			feedback: Feedback::None,
+			feedback_tween: Tween::finished(1.0),
 		};
-		shape.points.resize(num_points, Vec2d::new(0.0, 0.0));
-		shape.update(0.0, 0.0);
 		shape.set_target(in_channels);
+		shape.update(0.0, 0.0);
 		return shape;
 	}
 
-	fn reset(&mut self, in_shape: &Shape)
+	// Take over in_shape's round, morphing there from the current state.
+	fn reset(&mut self, in_shape: &Shape, morph_duration: f32)
 	{
-		self.position = in_shape.position;
+		self.morph_points.clear();
+		self.morph_points.extend_from_slice(&self.points);
+		self.morph_transform = self.current_transform();
+		self.morph_transform.rotation -= self.spin;
+		self.morph_spin = self.spin;
+		self.morph = Tween::new(0.0, 1.0, morph_duration, Easing::QuadInOut);
+		self.feedback = Feedback::None;
+
+		self.transform = in_shape.transform;
+		self.radius = in_shape.radius;
+		self.mode = in_shape.mode;
 		self.channel_targets = in_shape.channel_targets.clone();
 		self.set_channels = in_shape.set_channels.clone();
 		self.is_selected = false;
+		self.profile_valid = false;
 		self.update(0.0, 0.0);
 	}
 
+	// Grow out from the centre when there's no previous shape to morph from.
+	fn grow_in(&mut self, morph_duration: f32)
+	{
+		self.morph_points.clear();
+		self.morph_points.resize(self.points.len(), Vec2d::zero());
+		self.morph_transform = self.transform;
+		self.morph_spin = self.spin;
+		self.morph = Tween::new(0.0, 1.0, morph_duration, Easing::QuadInOut);
+	}
+
+	fn set_feedback(&mut self, in_feedback: Feedback, duration: f32)
+	{
+		self.feedback = in_feedback;
+		self.feedback_tween = Tween::new(0.0, 1.0, duration, Easing::Linear);
+	}
+
+	// Flashing on and off while showing a correct answer.
+	fn flash(&self) -> bool
+	{
+		match self.feedback
+		{
+			Feedback::Correct | Feedback::Reveal => (self.feedback_tween.progress() * 8.0) as i32 % 2 == 0,
+			_ => false,
+		}
+	}
+
+	// Transform with morph and feedback animation applied.
+	fn current_transform(&self) -> Transform
+	{
+		let m = self.morph.value();
+		let mut transform = self.transform;
+		transform.position = self.morph_transform.position.lerp(self.transform.position, m);
+		transform.rotation = self.morph_transform.rotation + (self.transform.rotation - self.morph_transform.rotation) * m + self.spin;
+		transform.scale = self.morph_transform.scale.lerp(self.transform.scale, m);
+
+		let f = self.feedback_tween.progress();
+		match self.feedback
+		{
+			Feedback::Correct =>
+			{
+				transform.scale *= 1.0 + FEEDBACK_GROW * (PI * tween::ease(Easing::QuadOut, f)).sin();
+			},
+			Feedback::Reveal =>
+			{
+				transform.scale *= 1.0 + FEEDBACK_GROW * 0.5 * (PI * f).sin();
+			},
+			Feedback::Wrong =>
+			{
+				transform.position.x += (f * PI * 8.0).sin() * FEEDBACK_SHAKE * (1.0 - f);
+			},
+			Feedback::None => {},
+		}
+		return transform;
+	}
+
 	fn set_target(&mut self, in_channels: [MixerChannel; 3])
 	{
-		let divisor = 440.0 / 8.0;
+		// One cycle around the outline per 55Hz, A1.
+		let divisor = pitch::A4_FREQ / 8.0;
 		for idx in 0..in_channels.len()
 		{
 			let (phase_inc, volume) = match in_channels[idx]
@@ -266,198 +340,269 @@ impl Shape
 		}
 
 		self.set_channels = in_channels;
+		self.profile_valid = false;
 	}
 
 	fn play_audio(&mut self, audio_tx: &Sender<MixerChannel>)
+	{
+		self.send_audio(audio_tx);
+		self.is_selected = true;
+	}
+
+	fn send_audio(&self, audio_tx: &Sender<MixerChannel>)
 	{
 		for idx in 0..3
 		{
 			audio_tx.send(self.set_channels[idx]);
+			audio_tx.send(MixerChannel::Pan(idx, self.pan(idx)));
 		}
-		self.is_selected = true;
+		audio_tx.send(MixerChannel::Trigger);
 	}
 	
+	// Hit-test in shape space so rotation, scale and skew are respected.
+	fn contains(&self, position: Vec2d) -> bool
+	{
+		match self.current_transform().to_matrix().inverse()
+		{
+			Some(inverse) => (inverse * position).magnitude() < self.radius,
+			None => false,
+		}
+	}
+
+	// Lissajous figures play their x waveform on the left and y on the right.
+	fn pan(&self, idx: usize) -> f32
+	{
+		match self.mode
+		{
+			ShapeMode::Lissajous { x_wave, y_wave, .. } if x_wave != y_wave =>
+			{
+				if idx == x_wave { -1.0 } else if idx == y_wave { 1.0 } else { 0.0 }
+			},
+			_ => 0.0,
+		}
+	}
+
+	fn sample_wave(&self, idx: usize, x: f32) -> f32
+	{
+		let channel = &self.channels[idx];
+		match idx
+		{
+			0 => sine_wave(x * channel.phase_inc) * channel.volume,
+			1 => square_wave(x * channel.phase_inc) * channel.volume,
+			2 => sawtooth_wave(x * channel.phase_inc) * channel.volume,
+			_ => 0.0,
+		}
+	}
+
 	fn sample_channels(&self, x: f32, t: f32) -> Vec2d
 	{
-		let size = SIZE;
-		let rot = (x + t * 0.125) * PI * 2.0;
-		let offset = Vec2d::new(rot.cos(), rot.sin());
+		if let ShapeMode::Lissajous { x_wave, y_wave, phase } = self.mode
+		{
+			// Volumes are 0.5 per waveform, so double to fill the radius.
+			let offset = (phase + t * SPIN_RATE) / self.channels[x_wave].phase_inc.max(1.0);
+			let x_sample = self.sample_wave(x_wave, x + offset) * 2.0;
+			let y_sample = self.sample_wave(y_wave, x) * 2.0;
+			return Vec2d::new(x_sample, y_sample) * self.radius * 1.5;
+		}
+
+		let size = self.radius;
+		let offset = Vec2d::from_angle(x * PI * 2.0);
 
 		let mut out_sample = 0.0;
 
-		let scale_rot = x;
 		for idx in 0..self.channels.len()
 		{
-			let channel = &self.channels[idx];
-			let sample = match idx
-			{
-				0 => sine_wave(scale_rot * channel.phase_inc) * channel.volume,
-				1 => square_wave(scale_rot * channel.phase_inc) * channel.volume,
-				2 => sawtooth_wave(scale_rot * channel.phase_inc) * channel.volume,
-				_ => 0.0,
-			};
-
-			out_sample += sample;
+			out_sample += self.sample_wave(idx, x);
 		}
 		
 		let scale = size * (out_sample / 3.0 + 1.0);
 		return offset * scale;
 	}
 
-	fn update(&mut self, tick: f32, time: f32)
+	// Points needed to follow the outline smoothly at its size on screen.
+	fn point_count(&self) -> usize
 	{
-		for idx in 0..self.channels.len()
-		{
-			self.channels[idx].phase_inc = self.channels[idx].phase_inc * 0.95 + self.channel_targets[idx].phase_inc * 0.05;
-			self.channels[idx].volume = self.channels[idx].volume * 0.95 + self.channel_targets[idx].volume * 0.05;
-		}
+		let scale = self.transform.scale.x.abs().max(self.transform.scale.y.abs());
+		let by_size = PI * 2.0 * self.radius * 1.5 * scale / PIXELS_PER_SEGMENT;
 
-		let num_points = self.points.len();
-		let mul_val = 1.0 / num_points as f32;
+		// Each wave cycle around the outline adds a bend to follow.
+		let mut cycles = 0.0;
+		for idx in 0..self.channel_targets.len()
 		{
-			for idx in 0..num_points
+			if self.channel_targets[idx].volume > 0.0
 			{
-				let point_a = self.sample_channels(idx as f32 * mul_val, time);
-				self.points[idx] = point_a;
+				cycles += self.channel_targets[idx].phase_inc;
 			}
 		}
+		let by_curvature = cycles * POINTS_PER_CYCLE;
+
+		let count = by_size.max(by_curvature) as usize;
+		count.max(MIN_SHAPE_POINTS).min(MAX_SHAPE_POINTS)
 	}
 
-	fn draw(&self, renderer: &mut Renderer, color: Color)
+	// Move channels towards their targets, returning true if any changed.
+	fn update_channels(&mut self) -> bool
 	{
-		renderer.set_draw_color(color);
-		let num_points = self.points.len();
-		for idx_a in 0..num_points
+		let mut changed = false;
+		for idx in 0..self.channels.len()
 		{
-			let idx_b = (idx_a + 1) % num_points;
-			let point_a = self.points[idx_a] + self.position;
-			let point_b = self.points[idx_b] + self.position;
-			draw_line(renderer, point_a, point_b);
+			let channel = &mut self.channels[idx];
+			let target = &self.channel_targets[idx];
+			if (channel.phase_inc - target.phase_inc).abs() < CHANNEL_EPSILON && (channel.volume - target.volume).abs() < CHANNEL_EPSILON
+			{
+				changed = changed || channel.phase_inc != target.phase_inc || channel.volume != target.volume;
+				channel.phase_inc = target.phase_inc;
+				channel.volume = target.volume;
+				continue;
+			}
+
+			channel.phase_inc = channel.phase_inc * 0.95 + target.phase_inc * 0.05;
+			channel.volume = channel.volume * 0.95 + target.volume * 0.05;
+			changed = true;
 		}
+		return changed;
 	}
-}
 
-fn get_time_seconds() -> f32
-{
-	precise_time_s() as f32
-}
-
-fn build_shapes(level: u32) -> Vec<Shape>
-{
-	let mut shapes = Vec::<Shape>::new();
-	let mut rng = rand::thread_rng();
+	fn update(&mut self, tick: f32, time: f32)
+	{
+		let channels_changed = self.update_channels();
 
-	let pos = &mut [
-		Vec2d::new(1.0 * WIDTH as f32 / 4.0, 2.0 * HEIGHT as f32 / 4.0),
-		Vec2d::new(3.0 * WIDTH as f32 / 4.0, 2.0 * HEIGHT as f32 / 4.0),
-		Vec2d::new(2.0 * WIDTH as f32 / 4.0, 2.0 * HEIGHT as f32 / 4.0),
-	];
+		self.morph.update(tick);
+		self.feedback_tween.update(tick);
 
-	if level >= 0
-	{
-		for shape_idx in 0..3
+		// Lissajous figures drift over time, so can't be cached or spun.
+		let animated = match self.mode
 		{
-			let channels = [
-				MixerChannel::Sine(440.0, if shape_idx == 0 { 0.5 } else { 0.0 } ),
-				MixerChannel::Square(440.0, if shape_idx == 1 { 0.5 } else { 0.0 } ),
-				MixerChannel::Sawtooth(440.0, if shape_idx == 2 { 0.5 } else { 0.0 } ),
-			];
+			ShapeMode::Lissajous { .. } => true,
+			ShapeMode::Polar => false,
+		};
+		self.spin = if animated { 0.0 } else { time * SPIN_RATE * PI * 2.0 };
 
-			shapes.push(Shape::new(Vec2d::new(0.0, 0.0), 1024, channels));
+		if channels_changed || animated || !self.profile_valid
+		{
+			let num_points = self.point_count();
+			let mul_val = 1.0 / num_points as f32;
+			self.profile.clear();
+			for idx in 0..num_points
+			{
+				let point = self.sample_channels(idx as f32 * mul_val, time);
+				self.profile.push(point);
+			}
+			self.profile_valid = true;
 		}
-	}
 
-	if level >= 5
-	{
-		for shape_idx in 0..3
-		{
-			let channels = [
-				MixerChannel::Sine(880.0, if shape_idx == 0 { 0.5 } else { 0.0 } ),
-				MixerChannel::Square(880.0, if shape_idx == 1 { 0.5 } else { 0.0 } ),
-				MixerChannel::Sawtooth(880.0, if shape_idx == 2 { 0.5 } else { 0.0 } ),
-			];
+		self.points.clear();
+		self.points.extend_from_slice(&self.profile);
 
-			shapes.push(Shape::new(Vec2d::new(0.0, 0.0), 1024, channels));
+		// Blend from the previous round's outline, holding it still while the new one spins.
+		let num_points = self.points.len();
+		let num_morph_points = self.morph_points.len();
+		if !self.morph.is_finished() && num_morph_points > 0
+		{
+			let m = self.morph.value();
+			let unspin = self.morph_spin - self.spin;
+			for idx in 0..num_points
+			{
+				let morph_point = self.morph_points[idx * num_morph_points / num_points].rotate(unspin);
+				self.points[idx] = morph_point.lerp(self.points[idx], m);
+			}
 		}
 	}
 
-	if level >= 10
+	fn draw(&self, renderer: &mut Renderer, transforms: &mut TransformStack, lines: &mut LineBatch, color: Color)
 	{
-		for shape_idx in 0..3
-		{
-			let channels = [
-				MixerChannel::Sine(220.0, if shape_idx == 0 { 0.5 } else { 0.0 } ),
-				MixerChannel::Square(220.0, if shape_idx == 1 { 0.5 } else { 0.0 } ),
-				MixerChannel::Sawtooth(220.0, if shape_idx == 2 { 0.5 } else { 0.0 } ),
-			];
-
-			shapes.push(Shape::new(Vec2d::new(0.0, 0.0), 1024, channels));
-		}
+		transforms.push(self.current_transform().to_matrix());
+		lines.thickness = SHAPE_THICKNESS;
+		lines.add_loop(&self.points, &transforms.top());
+		lines.flush(renderer, color);
+		transforms.pop();
 	}
-	
-	if level >= 20
+}
+
+fn find_shape_at(shapes: &Vec<Shape>, position: Vec2d) -> Option<usize>
+{
+	let mut found_idx = None;
+	for idx in 0..shapes.len()
 	{
-		for shape_idx in 0..3
+		if shapes[idx].contains(position)
 		{
-			let channels = [
-				MixerChannel::Sine(110.0, if shape_idx == 0 { 0.5 } else { 0.0 } ),
-				MixerChannel::Square(110.0, if shape_idx == 1 { 0.5 } else { 0.0 } ),
-				MixerChannel::Sawtooth(110.0, if shape_idx == 2 { 0.5 } else { 0.0 } ),
-			];
-
-			shapes.push(Shape::new(Vec2d::new(0.0, 0.0), 1024, channels));
+			found_idx = Some(idx);
 		}
 	}
-	
+	return found_idx;
+}
+
+fn find_target(shapes: &Vec<Shape>) -> Option<usize>
+{
+	shapes.iter().position(|shape| shape.is_selected)
+}
+
+fn get_time_seconds() -> f32
+{
+	precise_time_s() as f32
+}
 
-	if level >= 30
+// Shapes for a round, with the index of the target and the round's seed.
+fn build_shapes<R: Rng>(rng: &mut R, level: u32, bias: &Bias, screen_size: Vec2d) -> (Vec<Shape>, usize, u32, Family)
+{
+	let round = puzzle::generate_seeded(rng.gen::<u32>(), level, puzzle::candidate_count(level), bias);
+	// Lay out what was generated, which may be fewer than asked for.
+	let layout = layout_shapes(round.distractors.len() + 1, screen_size);
+
+	// Place target amongst the distractors.
+	let target_idx = rng.gen::<usize>() % layout.len();
+	let mut distractors = round.distractors.iter();
+	let mut shapes = Vec::<Shape>::with_capacity(layout.len());
+	for idx in 0..layout.len()
 	{
-		for shape_idx in 0..3
+		let sound = if idx == target_idx { round.target } else { *distractors.next().unwrap() };
+		let (position, radius) = layout[idx];
+		let mut shape = Shape::new(position, sound.to_channels());
+		shape.radius = radius;
+		if round.family == Family::Lissajous
 		{
-			let channels = [
-				MixerChannel::Sine(440.0, if shape_idx != 0 { 0.5 } else { 0.0 } ),
-				MixerChannel::Square(440.0, if shape_idx != 1 { 0.5 } else { 0.0 } ),
-				MixerChannel::Sawtooth(440.0, if shape_idx != 2 { 0.5 } else { 0.0 } ),
-			];
-
-			shapes.push(Shape::new(Vec2d::new(0.0, 0.0), 1024, channels));
+			let (x_wave, y_wave) = sound.lissajous_waves();
+			shape.mode = ShapeMode::Lissajous { x_wave: x_wave, y_wave: y_wave, phase: round.phase };
 		}
+		shapes.push(shape);
 	}
-		// Shuffle generated.
-	for idx in 0 ..shapes.len()
+
+	return (shapes, target_idx, round.seed, round.family);
+}
+
+// Lay out shapes on a centred grid, returning position and radius for each.
+fn layout_shapes(count: usize, screen_size: Vec2d) -> Vec<(Vec2d, f32)>
+{
+	let mut layout = Vec::with_capacity(count);
+	if count == 0
 	{
-		let swap_idx = rng.gen::<usize>() % shapes.len();
-		let val = shapes.swap_remove(swap_idx);
-		shapes.push(val);
+		return layout;
 	}
-	let mut new_shapes = Vec::<Shape>::new();
 
+	let rows = (count + MAX_SHAPES_PER_ROW - 1) / MAX_SHAPES_PER_ROW;
+	let cols = (count + rows - 1) / rows;
+	let cell_width = screen_size.x / cols as f32;
+	let cell_height = screen_size.y / rows as f32;
 
-	if level < 20
-	{
-		new_shapes.push(shapes.swap_remove(0));
-		new_shapes.push(shapes.swap_remove(0));
+	// Shapes extend up to 1.5x their radius, so keep 3 radii within a cell.
+	let radius = SIZE.min(cell_width.min(cell_height) / 3.0);
 
-		new_shapes[0].position = pos[0];
-		new_shapes[1].position = pos[1];
-	}
-	else 
+	for idx in 0..count
 	{
-		new_shapes.push(shapes.swap_remove(0));
-		new_shapes.push(shapes.swap_remove(0));
-		new_shapes.push(shapes.swap_remove(0));
-
-		new_shapes[0].position = pos[0];
-		new_shapes[1].position = pos[1];
-		new_shapes[2].position = pos[2];
+		let row = idx / cols;
+		let col = idx % cols;
+		let row_count = (count - row * cols).min(cols);
+		let row_offset = (cols - row_count) as f32 * cell_width * 0.5;
+		let position = Vec2d::new(
+			row_offset + (col as f32 + 0.5) * cell_width,
+			(row as f32 + 0.5) * cell_height);
+		layout.push((position, radius));
 	}
-
- 	return new_shapes;
+	return layout;
 }
 
-fn draw_char(renderer: &mut Renderer, position: Vec2d, scale: f32, color: Color, val: char)
+fn draw_char(lines_batch: &mut LineBatch, position: Vec2d, scale: f32, val: char)
 {
-	renderer.set_draw_color(color);
 	let mut lines = Vec::<Vec2d>::new();
 	match val
 	{
@@ -538,132 +683,594 @@ fn draw_char(renderer: &mut Renderer, position: Vec2d, scale: f32, color: Color,
 		'-' => {
 			lines.append(&mut vec![Vec2d::new(0.0, 1.0), Vec2d::new(1.0, 1.0)]);
 		}
-		_ => {}
-	}
-
-	for idx in 0..(lines.len() / 2)
-	{
-		let point_a = lines[idx * 2] * scale;
-		let point_b = lines[idx * 2 + 1] * scale;
-		draw_line(renderer, position + point_a, position + point_b);
-	}
-}
-
-fn draw_string(renderer: &mut Renderer, position: Vec2d, scale: f32, color: Color, vals: &String)
-{
-	let mut next_position = position;
-	for val in vals.chars()
-	{
-		draw_char(renderer, next_position, scale, color, val);
-		next_position = next_position + Vec2d::new(scale * 1.5, 0.0);
-	}
-}
-
-/////////////////////////////////////////////////////////////////////
-// Popup text
-struct PopupText
-{
-	position: Vec2d,
-	scale: f32,
-	color: Color,
-	time: f32,
-	text: String,
-}
-
-impl PopupText
-{
-	fn new(in_position: Vec2d, in_scale: f32, in_color: Color, in_time: f32, in_text: String) -> PopupText
-	{
-		PopupText
-		{
-			position: in_position,
-			scale: in_scale,
-			color: in_color,
-			time: in_time,
-			text: in_text,
+		'A' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(0.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(1.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 0.0), Vec2d::new(1.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 1.0), Vec2d::new(1.0, 1.0)]);
 		}
-	}
-
-	fn draw(&mut self, renderer: &mut Renderer, tick: f32) -> bool
-	{
-		draw_string(renderer, self.position, self.scale, self.color, &self.text);
-		self.position = self.position - Vec2d::new(0.0, self.scale * 4.0) * tick;
-		self.time -= tick;
-		return self.time > 0.0;
-	}
-}
-
-
-/////////////////////////////////////////////////////////////////////
-// main
-fn main()
-{
-	let ctx = sdl2::init().unwrap();
-	let video_ctx = ctx.video().unwrap();
-	let audio_ctx = ctx.audio().unwrap();
-
-	// Create window.
-	let window = match video_ctx.window("Oscillomatch by NeiloGD", WIDTH as u32, HEIGHT as u32).position_centered().opengl().build()
-	{
-		Ok(window) => window,
-		Err(err) => panic!("Failed to create window: {}", err)
-	};
-
-	// Setup audio.
-	let audio_spec = AudioSpecDesired
-	{
-		freq: Some(44100),
-		channels: Some(1),
-		samples: None
-	};
-	let (audio_tx, audio_rx) = channel();
-
-	let audio = audio_ctx.open_playback(None, &audio_spec, |spec|
-	{
-		MixerCallback
-		{
-			freq: spec.freq as f32,
-			rx: audio_rx,
-			channels:
-			[
-				MixerChannelParams::default(),
-				MixerChannelParams::default(),
-				MixerChannelParams::default(),
-				MixerChannelParams::default(),
-			],
-			channel_targets:
-			[
-				MixerChannelParams::default(),
-				MixerChannelParams::default(),
-				MixerChannelParams::default(),
-				MixerChannelParams::default(),
-			],
-			time: 0.0,
+		'B' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(0.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(0.75, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(0.75, 0.0), Vec2d::new(0.75, 1.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 1.0), Vec2d::new(1.0, 1.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 1.0), Vec2d::new(1.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(1.0, 2.0)]);
 		}
-	}).unwrap();
-	audio.resume();
+		'C' => {
+			lines.append(&mut vec![Vec2d::new(1.0, 0.0), Vec2d::new(0.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(0.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(1.0, 2.0)]);
+		}
+		'D' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(0.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(0.5, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(0.5, 0.0), Vec2d::new(1.0, 0.5)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 0.5), Vec2d::new(1.0, 1.5)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 1.5), Vec2d::new(0.5, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.5, 2.0), Vec2d::new(0.0, 2.0)]);
+		}
+		'E' => {
+			lines.append(&mut vec![Vec2d::new(1.0, 0.0), Vec2d::new(0.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(0.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(1.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 1.0), Vec2d::new(0.75, 1.0)]);
+		}
+		'F' => {
+			lines.append(&mut vec![Vec2d::new(1.0, 0.0), Vec2d::new(0.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(0.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 1.0), Vec2d::new(0.75, 1.0)]);
+		}
+		'G' => {
+			lines.append(&mut vec![Vec2d::new(1.0, 0.0), Vec2d::new(0.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(0.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(1.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 2.0), Vec2d::new(1.0, 1.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 1.0), Vec2d::new(0.5, 1.0)]);
+		}
+		'H' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(0.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 0.0), Vec2d::new(1.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 1.0), Vec2d::new(1.0, 1.0)]);
+		}
+		'I' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(1.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(0.5, 0.0), Vec2d::new(0.5, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(1.0, 2.0)]);
+		}
+		'J' => {
+			lines.append(&mut vec![Vec2d::new(1.0, 0.0), Vec2d::new(1.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 2.0), Vec2d::new(0.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(0.0, 1.5)]);
+		}
+		'K' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(0.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 1.0), Vec2d::new(1.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 1.0), Vec2d::new(1.0, 2.0)]);
+		}
+		'L' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(0.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(1.0, 2.0)]);
+		}
+		'M' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(0.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(0.5, 1.0)]);
+			lines.append(&mut vec![Vec2d::new(0.5, 1.0), Vec2d::new(1.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 0.0), Vec2d::new(1.0, 2.0)]);
+		}
+		'N' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(0.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(1.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 2.0), Vec2d::new(1.0, 0.0)]);
+		}
+		'O' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(1.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 0.0), Vec2d::new(1.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 2.0), Vec2d::new(0.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(0.0, 0.0)]);
+		}
+		'P' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(0.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(1.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 0.0), Vec2d::new(1.0, 1.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 1.0), Vec2d::new(0.0, 1.0)]);
+		}
+		'Q' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(1.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 0.0), Vec2d::new(1.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 2.0), Vec2d::new(0.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(0.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(0.5, 1.5), Vec2d::new(1.0, 2.0)]);
+		}
+		'R' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(0.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(1.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 0.0), Vec2d::new(1.0, 1.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 1.0), Vec2d::new(0.0, 1.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 1.0), Vec2d::new(1.0, 2.0)]);
+		}
+		'S' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(1.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(0.0, 1.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 1.0), Vec2d::new(0.0, 1.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 1.0), Vec2d::new(1.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(1.0, 2.0)]);
+		}
+		'T' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(1.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(0.5, 0.0), Vec2d::new(0.5, 2.0)]);
+		}
+		'U' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(0.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(1.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 2.0), Vec2d::new(1.0, 0.0)]);
+		}
+		'V' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(0.5, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.5, 2.0), Vec2d::new(1.0, 0.0)]);
+		}
+		'W' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(0.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(0.5, 1.0)]);
+			lines.append(&mut vec![Vec2d::new(0.5, 1.0), Vec2d::new(1.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 2.0), Vec2d::new(1.0, 0.0)]);
+		}
+		'Y' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(0.5, 1.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 0.0), Vec2d::new(0.5, 1.0)]);
+			lines.append(&mut vec![Vec2d::new(0.5, 1.0), Vec2d::new(0.5, 2.0)]);
+		}
+		'Z' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(1.0, 0.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 0.0), Vec2d::new(0.0, 2.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 2.0), Vec2d::new(1.0, 2.0)]);
+		}
+		'<' => {
+			lines.append(&mut vec![Vec2d::new(1.0, 0.0), Vec2d::new(0.0, 1.0)]);
+			lines.append(&mut vec![Vec2d::new(0.0, 1.0), Vec2d::new(1.0, 2.0)]);
+		}
+		'>' => {
+			lines.append(&mut vec![Vec2d::new(0.0, 0.0), Vec2d::new(1.0, 1.0)]);
+			lines.append(&mut vec![Vec2d::new(1.0, 1.0), Vec2d::new(0.0, 2.0)]);
+		}
+		':' => {
+			lines.append(&mut vec![Vec2d::new(0.5, 0.5), Vec2d::new(0.5, 0.6)]);
+			lines.append(&mut vec![Vec2d::new(0.5, 1.4), Vec2d::new(0.5, 1.5)]);
+		}
+		'.' => {
+			lines.append(&mut vec![Vec2d::new(0.5, 1.9), Vec2d::new(0.5, 2.0)]);
+		}
+		'/' => {
+			lines.append(&mut vec![Vec2d::new(1.0, 0.0), Vec2d::new(0.0, 2.0)]);
+		}
+		_ => {}
+	}
+
+	for idx in 0..(lines.len() / 2)
+	{
+		let point_a = lines[idx * 2] * scale;
+		let point_b = lines[idx * 2 + 1] * scale;
+		lines_batch.add_line(position + point_a, position + point_b);
+	}
+}
+
+fn draw_string(renderer: &mut Renderer, lines_batch: &mut LineBatch, position: Vec2d, scale: f32, color: Color, vals: &String)
+{
+	lines_batch.thickness = TEXT_THICKNESS;
+	let mut next_position = position;
+	for val in vals.chars()
+	{
+		draw_char(lines_batch, next_position, scale, val);
+		next_position = next_position + Vec2d::new(scale * 1.5, 0.0);
+	}
+	lines_batch.flush(renderer, color);
+}
+
+/////////////////////////////////////////////////////////////////////
+// Popup text
+struct PopupText
+{
+	position: Vec2d,
+	scale: f32,
+	color: Color,
+	time: f32,
+	text: String,
+}
+
+impl PopupText
+{
+	fn new(in_position: Vec2d, in_scale: f32, in_color: Color, in_time: f32, in_text: String) -> PopupText
+	{
+		PopupText
+		{
+			position: in_position,
+			scale: in_scale,
+			color: in_color,
+			time: in_time,
+			text: in_text,
+		}
+	}
+
+	fn draw(&mut self, renderer: &mut Renderer, lines: &mut LineBatch, tick: f32) -> bool
+	{
+		draw_string(renderer, lines, self.position, self.scale, self.color, &self.text);
+		self.position = self.position - Vec2d::new(0.0, self.scale * 4.0) * tick;
+		self.time -= tick;
+		return self.time > 0.0;
+	}
+}
+
+
+/////////////////////////////////////////////////////////////////////
+// Particle emitters
+fn hit_emitter(theme: &Theme) -> Emitter
+{
+	Emitter
+	{
+		kind: ParticleKind::Line,
+		min_speed: 120.0,
+		max_speed: 320.0,
+		spread: 0.3,
+		min_lifetime: 0.4,
+		max_lifetime: 0.9,
+		gravity: Vec2d::new(0.0, 200.0),
+		drag: 1.5,
+		length: 0.04,
+		start_color: theme.hit_spark,
+		end_color: with_alpha(theme.shape, 0),
+	}
+}
+
+fn miss_emitter(theme: &Theme) -> Emitter
+{
+	Emitter
+	{
+		kind: ParticleKind::Line,
+		min_speed: 200.0,
+		max_speed: 500.0,
+		spread: PI * 2.0,
+		min_lifetime: 0.15,
+		max_lifetime: 0.4,
+		gravity: Vec2d::new(0.0, 600.0),
+		drag: 3.0,
+		length: 0.02,
+		start_color: theme.miss_spark,
+		end_color: with_alpha(theme.miss, 0),
+	}
+}
+
+fn ambient_emitter(theme: &Theme) -> Emitter
+{
+	Emitter
+	{
+		kind: ParticleKind::Point,
+		min_speed: 8.0,
+		max_speed: 24.0,
+		spread: 0.5,
+		min_lifetime: 2.0,
+		max_lifetime: 5.0,
+		gravity: Vec2d::zero(),
+		drag: 0.0,
+		length: 0.0,
+		start_color: with_alpha(theme.noise, 64),
+		end_color: with_alpha(theme.noise, 0),
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// Scope
+const SCOPE_TRACE_SAMPLES: usize = 512;
+const SCOPE_XY_SAMPLES: usize = 1024;
+const SPECTRUM_MAX_FREQ: f32 = 4000.0;
+
+fn draw_trace(renderer: &mut Renderer, samples: &Vec<f32>, position: Vec2d, size: Vec2d, color: Color)
+{
+	renderer.set_draw_color(color);
+	let search = samples.len() - SCOPE_TRACE_SAMPLES;
+	let start = scope::find_trigger(&samples[..search], search);
+	let to_point = |idx: usize| position + Vec2d::new(
+		size.x * idx as f32 / SCOPE_TRACE_SAMPLES as f32,
+		size.y * 0.5 * (1.0 - samples[start + idx].max(-1.0).min(1.0)));
+
+	for idx in 1..SCOPE_TRACE_SAMPLES
+	{
+		draw_line(renderer, to_point(idx - 1), to_point(idx));
+	}
+}
+
+// Left channel on x and right on y, as a real XY scope would.
+fn draw_xy(renderer: &mut Renderer, left: &Vec<f32>, right: &Vec<f32>, center: Vec2d, size: f32, color: Color)
+{
+	renderer.set_draw_color(color);
+	let to_point = |idx: usize| center + Vec2d::new(left[idx], -right[idx]) * (size * 0.5);
+
+	for idx in 1..left.len().min(right.len())
+	{
+		draw_line(renderer, to_point(idx - 1), to_point(idx));
+	}
+}
+
+fn draw_spectrum(renderer: &mut Renderer, spectrum: &Vec<f32>, sample_rate: f32, position: Vec2d, size: Vec2d, color: Color)
+{
+	renderer.set_draw_color(color);
+	let bin_freq = sample_rate / (spectrum.len() * 2) as f32;
+	let num_bins = ((SPECTRUM_MAX_FREQ / bin_freq) as usize).min(spectrum.len());
+	for idx in 0..num_bins
+	{
+		// Decibel scale from -60dB to 0dB.
+		let db = 20.0 * spectrum[idx].max(1e-6).log10();
+		let height = size.y * ((db + 60.0) / 60.0).max(0.0).min(1.0);
+		let x = position.x + size.x * idx as f32 / num_bins as f32;
+		draw_line(renderer, Vec2d::new(x, position.y + size.y), Vec2d::new(x, position.y + size.y - height));
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// Benchmark
+const BENCH_SHAPES: usize = 64;
+const BENCH_FRAMES: usize = 600;
+const BENCH_LEVEL: u32 = 60;
+
+// Timing mode for --bench: updates many shapes without opening a window
+// and prints the cost per frame to stdout, like --help prints usage.
+fn run_benchmark(config: &Config)
+{
+	let mut rng = rand::thread_rng();
+	let screen_size = Vec2d::new(config.width as f32, config.height as f32);
+	let mut shapes = Vec::<Shape>::with_capacity(BENCH_SHAPES);
+	while shapes.len() < BENCH_SHAPES
+	{
+		let (new_shapes, _, _, _) = build_shapes(&mut rng, BENCH_LEVEL, &Bias::uniform(), screen_size);
+		shapes.extend(new_shapes.into_iter());
+	}
+	shapes.truncate(BENCH_SHAPES);
+
+	for &(name, lissajous, cached) in [("polar", false, true), ("polar uncached", false, false), ("lissajous", true, true)].iter()
+	{
+		for shape in shapes.iter_mut()
+		{
+			shape.mode = if lissajous
+			{
+				let (x_wave, y_wave) = Sound::from_channels(&shape.set_channels).lissajous_waves();
+				ShapeMode::Lissajous { x_wave: x_wave, y_wave: y_wave, phase: 0.0 }
+			}
+			else
+			{
+				ShapeMode::Polar
+			};
+			shape.profile_valid = false;
+		}
+
+		let tick = 1.0 / 60.0;
+		let start_time = get_time_seconds();
+		let mut num_points = 0;
+		for frame in 0..BENCH_FRAMES
+		{
+			for shape in shapes.iter_mut()
+			{
+				if !cached
+				{
+					shape.profile_valid = false;
+				}
+				shape.update(tick, frame as f32 * tick);
+				num_points += shape.points.len();
+			}
+		}
+		let elapsed = get_time_seconds() - start_time;
+
+		println!("{}: {} shapes, {:.3} ms per frame, {} points per shape",
+			name, BENCH_SHAPES, elapsed * 1000.0 / BENCH_FRAMES as f32, num_points / (BENCH_FRAMES * BENCH_SHAPES));
+	}
+}
+
+// Push settings the options menu can change to the mixer and CRT.
+fn apply_settings(config: &Config, audio_tx: &Sender<MixerChannel>, crt_settings: &mut CrtSettings)
+{
+	audio_tx.send(MixerChannel::Volume(Bus::Master, config.master_volume));
+	audio_tx.send(MixerChannel::Volume(Bus::Tone, config.tone_volume));
+	audio_tx.send(MixerChannel::Volume(Bus::Sfx, config.sfx_volume));
+	audio_tx.send(MixerChannel::Volume(Bus::Music, config.music_volume));
+	crt_settings.scanlines = config.scanlines > 0.0;
+	crt_settings.scanline_strength = config.scanlines;
+	crt_settings.noise = config.noise > 0.0;
+	crt_settings.noise_amount = config.noise;
+	crt_settings.noise_color = config.theme.noise.rgb();
+}
+
+/////////////////////////////////////////////////////////////////////
+// main
+fn print_warning(message: String)
+{
+	writeln!(std::io::stderr(), "Warning: {}", message).unwrap();
+}
+
+fn create_window(video_ctx: &VideoSubsystem, config: &Config) -> Result<Window, GameError>
+{
+	let mut window_builder = video_ctx.window(&config.title, config.width, config.height);
+	window_builder.position_centered().opengl();
+	if config.fullscreen
+	{
+		window_builder.fullscreen();
+	}
+	window_builder.build().map_err(|err| GameError::Window(config.width, config.height, err.to_string()))
+}
+
+// Accelerated renderer, or a software one if that fails.
+fn create_renderer(video_ctx: &VideoSubsystem, config: &Config) -> Result<Renderer<'static>, GameError>
+{
+	let window = try!(create_window(video_ctx, config));
+	let mut renderer_builder = window.renderer().accelerated();
+	if config.vsync
+	{
+		renderer_builder = renderer_builder.present_vsync();
+	}
+	match renderer_builder.build()
+	{
+		Ok(renderer) => return Ok(renderer),
+		Err(err) => print_warning(format!("No accelerated renderer ({}), falling back to software rendering.", err)),
+	}
+
+	// Building the renderer took the window, so make another.
+	let window = try!(create_window(video_ctx, config));
+	window.renderer().software().build().map_err(|err| GameError::Renderer(err.to_string()))
+}
+
+// Backend chosen in config. If it fails the game carries on silently.
+fn create_audio(ctx: &Sdl, config: &Config, mixer: Mixer) -> Box<AudioBackend>
+{
+	let result = match config.audio_backend
+	{
+		BackendKind::Sdl =>
+		{
+			let audio_spec = AudioSpecDesired
+			{
+				freq: Some(config.sample_rate),
+				channels: Some(2),
+				samples: config.buffer_size
+			};
+			match ctx.audio()
+			{
+				Ok(audio_ctx) =>
+				{
+					SdlBackend::open(&audio_ctx, config.audio_device.as_ref().map(|name| name.as_ref()), &audio_spec, mixer)
+						.map(|backend| Box::new(backend) as Box<AudioBackend>)
+						.map_err(|(err, mixer)|
+						{
+							let device = config.audio_device.as_ref().map_or("the default audio device".to_string(), |name| format!("audio device '{}'", name));
+							(format!("Failed to open {} ({}), continuing without sound. Check an output device is connected, or pick another with --audio-device.", device, err), mixer)
+						})
+				},
+				Err(err) => Err((format!("No audio ({}), continuing without sound.", err), mixer)),
+			}
+		},
+		BackendKind::Capture =>
+		{
+			CaptureBackend::create(&config.capture_file, mixer)
+				.map(|backend| Box::new(backend) as Box<AudioBackend>)
+				.map_err(|(err, mixer)| (format!("Capture failed ({}), continuing without sound.", err), mixer))
+		},
+		BackendKind::Null => Ok(Box::new(NullBackend::new(mixer)) as Box<AudioBackend>),
+	};
+	let backend = match result
+	{
+		Ok(backend) => backend,
+		Err((message, mixer)) =>
+		{
+			print_warning(message);
+			Box::new(NullBackend::new(mixer)) as Box<AudioBackend>
+		},
+	};
+	println!("Audio: {}", backend.name());
+	return backend;
+}
+
+// Send tones out and read pads in over MIDI, if set up in config.
+#[cfg(feature = "midi")]
+fn open_midi(config: &Config, mixer: &mut Mixer, input_sources: &mut Vec<Box<InputSource>>)
+{
+	match midi::MidiTones::open(&config.midi_output)
+	{
+		Ok(Some(tones)) =>
+		{
+			let tones_tx = tones.spawn();
+			// Sending fails only once the MIDI thread has gone.
+			mixer.set_listener(move |message| { let _ = tones_tx.send(*message); });
+		},
+		Ok(None) => {},
+		Err(err) => print_warning(format!("No MIDI output ({}), continuing without it.", err)),
+	}
+	match midi::MidiPads::open(&config.midi_input, config.midi_first_pad)
+	{
+		Ok(Some(pads)) => input_sources.push(Box::new(pads)),
+		Ok(None) => {},
+		Err(err) => print_warning(format!("No MIDI input ({}), continuing without it.", err)),
+	}
+}
+
+#[cfg(not(feature = "midi"))]
+fn open_midi(config: &Config, _mixer: &mut Mixer, _input_sources: &mut Vec<Box<InputSource>>)
+{
+	if config.midi_output != config::MidiPort::None || config.midi_input != config::MidiPort::None
+	{
+		print_warning("MIDI is set up in the config, but this build has no MIDI support. Rebuild with --features midi.".to_string());
+	}
+}
+
+fn main()
+{
+	if let Err(err) = run()
+	{
+		writeln!(std::io::stderr(), "Error: {}", err).unwrap();
+		std::process::exit(1);
+	}
+}
+
+fn run() -> Result<(), GameError>
+{
+	let args: Vec<String> = std::env::args().skip(1).collect();
+	let command_line = try!(CommandLine::parse(&args));
+	if command_line.help
+	{
+		println!("{}", USAGE);
+		return Ok(());
+	}
+
+	let mut config = try!(Config::load(&command_line.config_filename));
+	try!(config.apply(&command_line));
+
+	if command_line.bench
+	{
+		run_benchmark(&config);
+		return Ok(());
+	}
+
+	let width = config.width as i32;
+	let height = config.height as i32;
+	let screen_size = Vec2d::new(width as f32, height as f32);
+
+	let ctx = try!(sdl2::init().map_err(GameError::Init));
+	let video_ctx = try!(ctx.video().map_err(GameError::Video));
+
+	// Setup audio.
+	let (audio_tx, audio_rx) = channel();
+	let scope = Arc::new(Mutex::new(ScopeBuffer::new(SCOPE_BUFFER_SIZE, config.sample_rate as f32)));
+	let mut mixer = Mixer::new(config.sample_rate, audio_rx, scope.clone(), [config.master_volume, config.tone_volume, config.sfx_volume, config.music_volume]);
+	let mut input_sources: Vec<Box<InputSource>> = Vec::new();
+	let mut input_events = Vec::new();
+	open_midi(&config, &mut mixer, &mut input_sources);
+	let mut audio = create_audio(&ctx, &config, mixer);
 
 	// Setup renderer.
-	let mut renderer = window.renderer().build().unwrap();
+	let mut renderer = try!(create_renderer(&video_ctx, &config));
+	let mut transforms = TransformStack::new();
+	let mut lines = LineBatch::new(width as usize, height as usize, SHAPE_THICKNESS);
+
+	let mut theme = config.theme;
 
-	renderer.set_draw_color(Color::RGB(0, 0, 0));
+	renderer.set_draw_color(theme.background);
 	renderer.clear();
 	renderer.present();
 
-	let mut event_pump = ctx.event_pump().unwrap();
+	let mut event_pump = try!(ctx.event_pump().map_err(GameError::Events));
 
-	let mut position = Vec2d::new(WIDTH as f32, HEIGHT as f32) * 0.5;
+	let mut position = Vec2d::new(width as f32, height as f32) * 0.5;
 
 	let mut time = 0.0;
 	let mut tick = 0.0;
 	let mut last_time = get_time_seconds();
 
-	let mut level = 1;
+	let mut level = config.start_level;
 	let mut score = 0;
 	let mut score_multiplier = 1;
 	let mut high_score = 0;
+	audio_tx.send(MixerChannel::MusicIntensity(music::intensity(level, score_multiplier - 1)));
 
 	let high_score_filename = "highscore.txt";
+	let stats_filename = "stats.txt";
+	let mut stats = PlayerStats::load(stats_filename);
+	let achievements_filename = "achievements.txt";
+	let mut achievements = Achievements::load(achievements_filename);
+	let mut streak = Streak::new();
 
 	match File::open(high_score_filename)
 	{
@@ -688,93 +1295,429 @@ fn main()
 	let mut mult = 1.0;
 	let mut mouse_pos = Vec2d::new(0.0, 0.0);
 	let mut rng = rand::thread_rng();
-	let selected_shape_idx = rng.gen::<usize>() % 2;
-	shapes = build_shapes(level);
-	shapes[selected_shape_idx].play_audio(&audio_tx);
+	let mut round_rng: StdRng = match config.seed
+	{
+		Some(seed) => SeedableRng::from_seed(&[seed as usize][..]),
+		None => SeedableRng::from_seed(&[rng.gen::<usize>()][..]),
+	};
+	let mut bias = stats.bias();
+	bias.family = config.mode;
+	bias.tuning = config.tuning;
+	let (new_shapes, selected_shape_idx, mut round_seed, mut round_family) = build_shapes(&mut round_rng, level, &bias, screen_size);
+	let mut round_level = level;
+	shapes = new_shapes;
+	// The target plays once the countdown ends.
+	shapes[selected_shape_idx].is_selected = true;
+
+	// Countdown state, and the last number shown.
+	let mut countdown = COUNTDOWN_SECONDS as f32;
+	let mut countdown_shown = 0;
+
+	// Preview state.
+	let mut preview_mode = false;
+	let mut preview_idx = None;
+	let mut hover_idx = None;
+
+	// Round transition state.
+	let morph_duration = MORPH_DURATION;
+	let mut next_round_time = None;
+
+	// Particle state.
+	let mut particles = ParticleSystem::new(MAX_PARTICLES);
+	let mut ambient_spawn = 0.0;
+
+	// Scope state.
+	let mut scope_debug = false;
+	let mut scope_hint = false;
+	let mut scope_samples = Vec::<f32>::with_capacity(SCOPE_BUFFER_SIZE);
+	let mut scope_left = Vec::<f32>::with_capacity(SCOPE_BUFFER_SIZE);
+	let mut scope_right = Vec::<f32>::with_capacity(SCOPE_BUFFER_SIZE);
+	let mut scope_spectrum = Vec::<f32>::with_capacity(FFT_SIZE / 2);
+
+	// CRT state.
+	let mut crt_settings = CrtSettings::default();
+	apply_settings(&config, &audio_tx, &mut crt_settings);
+	let mut crt = CrtStage::new(&renderer, width as u32, height as u32);
+	let mut preview_end_time = 0.0;
+	let mut preview_count = 0;
+
+	// Options state.
+	let mut options = OptionsMenu::new();
+	let mut fullscreen = config.fullscreen;
+	let mut show_achievements = false;
+	// Whether dimming behind menus has failed, so it's only reported once.
+	let mut dim_failed = false;
 
 	'running: loop
 	{
+		// Shape answered this frame, and where to show the result.
+		let mut pick: Option<(usize, Vec2d)> = None;
 		for event in event_pump.poll_iter()
 		{
 			match event
 			{
 				Event::Quit {..} => break 'running,
-				Event::MouseMotion { x, y, .. } => 
+				Event::KeyDown { keycode: Some(..), .. } if show_achievements =>
 				{
-					mouse_pos = Vec2d::new(x as f32, y as f32);
+					// Any key closes the achievements screen.
+					show_achievements = false;
+					audio_tx.send(MixerChannel::Sfx(Sfx::MenuSelect));
 				},
-				Event::MouseButtonDown { x, y, .. } =>
+				Event::KeyDown { keycode: Some(key), .. } if options.is_open =>
 				{
-					mouse_pos = Vec2d::new(x as f32, y as f32);
-					let mut selected_idx = -1;
-					for idx in 0..shapes.len()
+					let changed = options.handle_key(key, &mut config);
+					if changed || key == Keycode::Return || key == Keycode::Space
+					{
+						audio_tx.send(MixerChannel::Sfx(Sfx::MenuSelect));
+					}
+					else if key == Keycode::Up || key == Keycode::Down
+					{
+						audio_tx.send(MixerChannel::Sfx(Sfx::MenuMove));
+					}
+					if changed
 					{
-						let mut shape = &mut shapes[idx];
-						if (mouse_pos - shape.position).magnitude() < SIZE
+						apply_settings(&config, &audio_tx, &mut crt_settings);
+						theme = config.theme;
+						if config.fullscreen != fullscreen
 						{
-							selected_idx = idx as i32;
+							let fullscreen_type = if config.fullscreen { FullscreenType::True } else { FullscreenType::Off };
+							if renderer.window_mut().unwrap().set_fullscreen(fullscreen_type).is_ok()
+							{
+								fullscreen = config.fullscreen;
+							}
 						}
 					}
 
-					if selected_idx != -1
+					if !options.is_open
 					{
-						if shapes[selected_idx as usize].is_selected == true
+						if let Err(err) = config.save_options(&command_line.config_filename)
 						{
-							let add_score = 10 * score_multiplier;
-							score = score + add_score;
-							level = level + 1;
-							score_multiplier = score_multiplier + 1;
-
-							popup_texts.push(PopupText::new(mouse_pos, 32.0, Color::RGB(0, 255, 0), 2.0, format!("+{}", add_score).to_string()));
-
-							audio_tx.send(MixerChannel::Beep(1670.0));
+							writeln!(std::io::stderr(), "Failed to save options: {}", err).unwrap();
 						}
-						else 
+					}
+				},
+				Event::KeyDown { keycode: Some(key), .. } if config.bindings.action(key).is_some() =>
+				{
+					match config.bindings.action(key)
+					{
+						Some(Action::Replay) =>
 						{
-							let sub_score = (score * 1) / 4;
-							score = score - sub_score;
-							level = (level * 3) / 4;
-							if score < 0 
+							// Replay the target sound from the start of its envelope.
+							if let Some(target_idx) = find_target(&shapes)
 							{
-								score = 0;
+								shapes[target_idx].send_audio(&audio_tx);
 							}
-							if level < 1
+							preview_idx = None;
+						},
+						Some(Action::Preview) =>
+						{
+							preview_mode = !preview_mode;
+						},
+						Some(Action::Hint) =>
+						{
+							scope_hint = !scope_hint;
+						},
+						Some(Action::Options) =>
+						{
+							options.open();
+							audio_tx.send(MixerChannel::Sfx(Sfx::MenuSelect));
+						},
+						Some(Action::Achievements) =>
+						{
+							show_achievements = true;
+							audio_tx.send(MixerChannel::Sfx(Sfx::MenuSelect));
+						},
+						Some(Action::Export) =>
+						{
+							if let Some(target_idx) = find_target(&shapes)
 							{
-								level = 1;
+								let round = RoundExport
+								{
+									level: round_level,
+									round_seed: round_seed,
+									game_seed: config.seed,
+									target_idx: target_idx,
+									candidates: shapes.iter().map(|shape|
+									{
+										let matrix = shape.current_transform().to_matrix();
+										Candidate
+										{
+											channels: shape.set_channels,
+											pans: [shape.pan(0), shape.pan(1), shape.pan(2)],
+											outline: shape.points.iter().map(|&point| matrix * point).collect(),
+										}
+									}).collect(),
+								};
+								match export::export_round(&round, config.sample_rate, screen_size, &theme)
+								{
+									Ok(..) => popup_texts.push(PopupText::new(screen_size * 0.5, 32.0, theme.high_score, 2.0, "SAVED".to_string())),
+									Err(err) => print_warning(format!("Failed to export round: {}", err)),
+								}
 							}
-							score_multiplier = 1;
-							popup_texts.push(PopupText::new(mouse_pos, 32.0, Color::RGB(255, 0, 0), 2.0, format!("-{}", sub_score).to_string()));
-
-							audio_tx.send(MixerChannel::Beep(110.0));
-						}
-
-						if score > high_score
-						{
-							high_score = score;
-							let mut file = File::create(high_score_filename);
-							file.unwrap().write_fmt(format_args!("{}", high_score));
-						}
-
-						let new_shapes = build_shapes(level);
-						let selected_shape_idx = rng.gen::<usize>() % new_shapes.len();
-						if shapes.len() == new_shapes.len()
+						},
+						None => {},
+					}
+				},
+				Event::KeyDown { keycode: Some(Keycode::F1), .. } =>
+				{
+					scope_debug = !scope_debug;
+				},
+				Event::KeyDown { keycode: Some(Keycode::F2), .. } =>
+				{
+					crt_settings.persistence = !crt_settings.persistence;
+				},
+				Event::KeyDown { keycode: Some(Keycode::F3), .. } =>
+				{
+					crt_settings.bloom = !crt_settings.bloom;
+				},
+				Event::KeyDown { keycode: Some(Keycode::F4), .. } =>
+				{
+					crt_settings.curvature = !crt_settings.curvature;
+				},
+				Event::KeyDown { keycode: Some(Keycode::F5), .. } =>
+				{
+					// Cycle scanline strength, off after the strongest.
+					let strengths = [0.125, 0.25, 0.5];
+					crt_settings.scanlines = match strengths.iter().position(|&strength| strength == crt_settings.scanline_strength)
+					{
+						Some(idx) if crt_settings.scanlines =>
 						{
-							for idx in 0..shapes.len()
+							if idx + 1 < strengths.len()
 							{
-								shapes[idx].reset(&new_shapes[idx]);
+								crt_settings.scanline_strength = strengths[idx + 1];
+								true
 							}
-						}
-						else
+							else
+							{
+								false
+							}
+						},
+						_ =>
+						{
+							crt_settings.scanline_strength = strengths[0];
+							true
+						},
+					};
+				},
+				Event::KeyDown { keycode: Some(Keycode::F6), .. } =>
+				{
+					crt_settings.noise = !crt_settings.noise;
+				},
+				Event::KeyDown { keycode: Some(Keycode::F7), .. } =>
+				{
+					config.theme = config.theme.next();
+					theme = config.theme;
+					crt_settings.noise_color = theme.noise.rgb();
+				},
+				Event::MouseMotion { x, y, .. } if !options.is_open && !show_achievements =>
+				{
+					mouse_pos = Vec2d::new(x as f32, y as f32);
+
+					// Audition hovered shape when previewing.
+					let new_hover_idx = find_shape_at(&shapes, mouse_pos);
+					if preview_mode && new_hover_idx != hover_idx
+					{
+						if let Some(idx) = new_hover_idx
 						{
-							shapes = new_shapes;
-						}						
-						shapes[selected_shape_idx].play_audio(&audio_tx);
+							shapes[idx].send_audio(&audio_tx);
+							preview_idx = new_hover_idx;
+							preview_end_time = time + PREVIEW_TIME;
+							preview_count = preview_count + 1;
+						}
 					}
+					hover_idx = new_hover_idx;
+				},
+				Event::MouseButtonDown { x, y, .. } if !options.is_open && !show_achievements =>
+				{
+					mouse_pos = Vec2d::new(x as f32, y as f32);
+					pick = find_shape_at(&shapes, mouse_pos).map(|idx| (idx, mouse_pos));
 				},
 				_ => {},
 			}
 		}
 
+		// Pads and other devices pick shapes directly.
+		input_events.clear();
+		for source in input_sources.iter_mut()
+		{
+			source.poll(&mut input_events);
+		}
+		for event in input_events.iter()
+		{
+			match *event
+			{
+				InputEvent::Pick(idx) if idx < shapes.len() && !options.is_open && !show_achievements => pick = Some((idx, shapes[idx].transform.position)),
+				_ => {},
+			}
+		}
+
+		if let Some((selected_idx, pick_position)) = pick
+		{
+			if next_round_time.is_none() && countdown <= 0.0
+			{
+				// Track accuracy so later rounds favour weak spots.
+				if let Some(target_idx) = find_target(&shapes)
+				{
+					let target = Sound::from_channels(&shapes[target_idx].set_channels);
+					let picked = Sound::from_channels(&shapes[selected_idx].set_channels);
+					let hit = target_idx == selected_idx;
+					// Stats categories are waveform mixes, so only timbre rounds count.
+					if round_family == Family::Timbre
+					{
+						stats.record(&target, &picked, hit);
+						if let Err(err) = stats.save(stats_filename)
+						{
+							print_warning(format!("Failed to save stats: {}", err));
+						}
+					}
+
+					if let Some(length) = streak.record(hit)
+					{
+						popup_texts.push(PopupText::new(pick_position + Vec2d::new(0.0, 48.0), 24.0, theme.high_score, 2.0, format!("STREAK {}", length)));
+						audio_tx.send(MixerChannel::Sfx(Sfx::Streak));
+					}
+
+					let unlocked = achievements.record(&target, hit, round_level, &streak);
+					for (idx, achievement) in unlocked.iter().enumerate()
+					{
+						// Centred, with each character 1.5 times the scale wide.
+						let text = format!("UNLOCKED: {}", achievement.title());
+						let position = Vec2d::new(screen_size.x * 0.5 - text.len() as f32 * 12.0, screen_size.y * 0.25 + idx as f32 * 48.0);
+						popup_texts.push(PopupText::new(position, 16.0, theme.high_score, 3.0, text));
+					}
+					if !unlocked.is_empty()
+					{
+						audio_tx.send(MixerChannel::Sfx(Sfx::Achievement));
+					}
+					if let Err(err) = achievements.save(achievements_filename)
+					{
+						print_warning(format!("Failed to save achievements: {}", err));
+					}
+				}
+
+				if shapes[selected_idx].is_selected == true
+				{
+					let mut add_score = 10 * score_multiplier - PREVIEW_PENALTY * preview_count;
+					if add_score < 0
+					{
+						add_score = 0;
+					}
+					score = score + add_score;
+					level = level + 1;
+					score_multiplier = score_multiplier + 1;
+
+					popup_texts.push(PopupText::new(pick_position, 32.0, theme.hit, 2.0, format!("+{}", add_score).to_string()));
+					shapes[selected_idx].set_feedback(Feedback::Correct, FEEDBACK_DURATION);
+
+					let shape = &shapes[selected_idx];
+					particles.burst_along(&mut rng, &hit_emitter(&theme), &shape.points, &shape.current_transform().to_matrix(), 8);
+
+					audio_tx.send(MixerChannel::Sfx(Sfx::Hit));
+					if puzzle::candidate_count(level) > puzzle::candidate_count(level - 1)
+					{
+						audio_tx.send(MixerChannel::Sfx(Sfx::LevelUp));
+					}
+				}
+				else 
+				{
+					let sub_score = (score * 1) / 4;
+					score = score - sub_score;
+					level = (level * 3) / 4;
+					if score < 0 
+					{
+						score = 0;
+					}
+					if level < 1
+					{
+						level = 1;
+					}
+					score_multiplier = 1;
+					popup_texts.push(PopupText::new(pick_position, 32.0, theme.miss, 2.0, format!("-{}", sub_score).to_string()));
+					shapes[selected_idx].set_feedback(Feedback::Wrong, FEEDBACK_DURATION);
+					particles.burst(&mut rng, &miss_emitter(&theme), pick_position, 48);
+					if let Some(target_idx) = find_target(&shapes)
+					{
+						shapes[target_idx].set_feedback(Feedback::Reveal, FEEDBACK_DURATION);
+					}
+
+					audio_tx.send(MixerChannel::Sfx(Sfx::Miss));
+				}
+
+				if score > high_score
+				{
+					high_score = score;
+					if let Ok(mut file) = File::create(high_score_filename)
+					{
+						file.write_fmt(format_args!("{}", high_score));
+					}
+				}
+
+				next_round_time = Some(time + FEEDBACK_DURATION);
+				audio_tx.send(MixerChannel::MusicIntensity(music::intensity(level, score_multiplier - 1)));
+			}
+		}
+
+		// Start next round once answer feedback has played.
+		if next_round_time.map_or(false, |round_time| time >= round_time)
+		{
+			let mut bias = stats.bias();
+			bias.family = config.mode;
+			bias.tuning = config.tuning;
+			let (new_shapes, selected_shape_idx, new_round_seed, new_round_family) = build_shapes(&mut round_rng, level, &bias, screen_size);
+			round_seed = new_round_seed;
+			round_family = new_round_family;
+			round_level = level;
+			shapes.truncate(new_shapes.len());
+			for (idx, new_shape) in new_shapes.into_iter().enumerate()
+			{
+				if idx < shapes.len()
+				{
+					shapes[idx].reset(&new_shape, morph_duration);
+				}
+				else
+				{
+					let mut shape = new_shape;
+					shape.grow_in(morph_duration);
+					shapes.push(shape);
+				}
+			}
+			shapes[selected_shape_idx].play_audio(&audio_tx);
+
+			preview_idx = None;
+			preview_count = 0;
+			next_round_time = None;
+		}
+
+		// Count in the first round, ticking each second.
+		if countdown > 0.0
+		{
+			let number = countdown.ceil() as i32;
+			if number != countdown_shown
+			{
+				countdown_shown = number;
+				audio_tx.send(MixerChannel::Sfx(Sfx::CountdownTick));
+				popup_texts.push(PopupText::new(screen_size * 0.5, 32.0, theme.score, 1.0, number.to_string()));
+			}
+			countdown -= tick;
+			if countdown <= 0.0
+			{
+				if let Some(target_idx) = find_target(&shapes)
+				{
+					shapes[target_idx].send_audio(&audio_tx);
+				}
+			}
+		}
+
+		// Return to target sound once preview has finished.
+		if preview_idx.is_some() && time > preview_end_time && countdown <= 0.0
+		{
+			if let Some(target_idx) = find_target(&shapes)
+			{
+				shapes[target_idx].send_audio(&audio_tx);
+			}
+			preview_idx = None;
+		}
+
 		// Update shapes.
 		for idx in 0..shapes.len()
 		{
@@ -782,21 +1725,20 @@ fn main()
 			shape.update(tick, time);
 		}
 
-		// Clear screen.
-		renderer.set_draw_color(Color::RGBA(0, 0, 0, 20));
-		renderer.set_blend_mode(BlendMode::Blend);
-		renderer.fill_rect(Rect::new(0, 0, WIDTH as u32, HEIGHT as u32));
-
-		// Draw noise.
+		// Update particles, drifting ambient motes up the screen.
+		ambient_spawn += AMBIENT_RATE * tick;
+		while ambient_spawn >= 1.0
 		{
-			renderer.set_draw_color(Color::RGBA(0, 255, 0, 32));
-			for idx in 0..4096
-			{
-				let x = rng.gen::<i32>() % WIDTH as i32;
-				let y = rng.gen::<i32>() % HEIGHT as i32;
+			let position = Vec2d::new(rng.gen::<f32>() * width as f32, rng.gen::<f32>() * height as f32);
+			particles.spawn(&mut rng, &ambient_emitter(&theme), position, -PI * 0.5);
+			ambient_spawn -= 1.0;
+		}
+		particles.update(tick);
 
-				renderer.draw_point(Point::new(x, y));
-			}
+		// Clear screen, into the CRT stage's scene.
+		if let Err(err) = crt.begin_frame(&mut renderer, &crt_settings, theme.background)
+		{
+			print_warning(format!("CRT effects are off: {}", err));
 		}
 
 		// Draw shapes.
@@ -804,14 +1746,62 @@ fn main()
 		{
 			let mut shape = &shapes[idx];
 
-			let color = if (mouse_pos - shape.position).magnitude() < SIZE { Color::RGB(0, 255, 0) } else { Color::RGB(0, 128, 0) };
+			let color = if shape.flash()
+			{
+				theme.shape_correct
+			}
+			else if shape.feedback == Feedback::Wrong
+			{
+				theme.shape_wrong
+			}
+			else if shape.contains(mouse_pos)
+			{
+				theme.shape_hover
+			}
+			else
+			{
+				theme.shape
+			};
 
-			shape.draw(&mut renderer, color);
+			shape.draw(&mut renderer, &mut transforms, &mut lines, color);
 		}
 
+		// Draw particles.
+		particles.draw(&mut renderer);
+
 		// Draw score.
-		draw_string(&mut renderer, Vec2d::new(128.0, 128.0 - 40.0), 16.0, Color::RGB(0, 128, 128), &high_score.to_string());
-		draw_string(&mut renderer, Vec2d::new(128.0, 128.0), 16.0, Color::RGB(0, 128, 0), &score.to_string());
+		draw_string(&mut renderer, &mut lines, Vec2d::new(128.0, 128.0 - 40.0), 16.0, theme.high_score, &high_score.to_string());
+		draw_string(&mut renderer, &mut lines, Vec2d::new(128.0, 128.0), 16.0, theme.score, &score.to_string());
+
+		// Draw scope.
+		if scope_debug || scope_hint
+		{
+			let sample_rate;
+			{
+				let buffer = scope.lock().unwrap();
+				buffer.read_latest(&mut scope_samples, SCOPE_BUFFER_SIZE);
+				buffer.read_latest_stereo(&mut scope_left, &mut scope_right, SCOPE_XY_SAMPLES);
+				sample_rate = buffer.sample_rate;
+			}
+
+			let trace_size = Vec2d::new(256.0, 64.0);
+			if scope_hint
+			{
+				let trace_position = Vec2d::new((width as f32 - trace_size.x) * 0.5, height as f32 - trace_size.y - 32.0);
+				draw_trace(&mut renderer, &scope_samples, trace_position, trace_size, theme.scope);
+			}
+
+			if scope_debug
+			{
+				let overlay_position = Vec2d::new(width as f32 - trace_size.x - 32.0, 32.0);
+				draw_trace(&mut renderer, &scope_samples, overlay_position, trace_size, theme.scope_debug);
+				draw_xy(&mut renderer, &scope_left, &scope_right, overlay_position + Vec2d::new(64.0, 80.0), 128.0, theme.scope_debug);
+
+				let fft_start = scope_samples.len() - FFT_SIZE;
+				scope::spectrum(&scope_samples[fft_start..], &mut scope_spectrum);
+				draw_spectrum(&mut renderer, &scope_spectrum, sample_rate, overlay_position + Vec2d::new(0.0, 224.0), trace_size, theme.scope_debug);
+			}
+		}
 
 		// Draw popups.
 		{
@@ -822,7 +1812,7 @@ fn main()
 				{
 					break 'popup
 				}
-				if !popup_texts[idx].draw(&mut renderer, tick)
+				if !popup_texts[idx].draw(&mut renderer, &mut lines, tick)
 				{
 					popup_texts.remove(idx);
 				}
@@ -833,17 +1823,37 @@ fn main()
 			}
 		}
 
-		// Draw scanlines.
+		// Draw options or achievements over the game.
+		let menu_result = if options.is_open
 		{
-			renderer.set_draw_color(Color::RGBA(0, 0, 0, 32));
-			let mut y = 0.0;
-			while y < HEIGHT as f32
+			options.draw(&mut renderer, &mut lines, &config, &theme, screen_size)
+		}
+		else if show_achievements
+		{
+			achievements.draw(&mut renderer, &mut lines, &streak, &theme, screen_size)
+		}
+		else
+		{
+			Ok(())
+		};
+		if let Err(err) = menu_result
+		{
+			if !dim_failed
 			{
+				dim_failed = true;
+				print_warning(format!("Failed to dim the game behind menus: {}", err));
+			}
+		}
 
-				draw_line(&mut renderer, Vec2d::new(0.0, y), Vec2d::new(WIDTH as f32, y as f32));
+		if let Some(err) = lines.take_error()
+		{
+			print_warning(format!("Failed to draw lines: {}", err));
+		}
 
-				y += 3.0;
-			}
+		// Post-process.
+		if let Err(err) = crt.end_frame(&mut renderer, &crt_settings)
+		{
+			print_warning(format!("CRT effects are off: {}", err));
 		}
 
 		renderer.present();
@@ -853,5 +1863,9 @@ fn main()
 		tick = next_time - last_time;
 		last_time = next_time;
 		time = time + tick;
+
+		audio.update(tick);
 	}
+
+	return Ok(());
 }
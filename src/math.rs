@@ -1,8 +1,8 @@
-use std::ops::{ Add, Sub, Mul, Div };
+use std::ops::{ Add, Sub, Mul, Div, Neg, AddAssign, SubAssign, MulAssign, DivAssign };
 
 /////////////////////////////////////////////////////////////////////
 // Vec2d
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Vec2d
 {
 	pub x: f32,
@@ -20,10 +20,88 @@ impl Vec2d
 		}
 	}
 
+	pub fn zero() -> Vec2d
+	{
+		Vec2d::new(0.0, 0.0)
+	}
+
+	// Unit vector pointing at angle in radians.
+	pub fn from_angle(angle: f32) -> Vec2d
+	{
+		Vec2d::new(angle.cos(), angle.sin())
+	}
+
 	pub fn magnitude(&self) -> f32
 	{
 		(self.x * self.x + self.y * self.y).sqrt()
 	}
+
+	pub fn magnitude_squared(&self) -> f32
+	{
+		self.x * self.x + self.y * self.y
+	}
+
+	pub fn dot(&self, rhs: Vec2d) -> f32
+	{
+		self.x * rhs.x + self.y * rhs.y
+	}
+
+	// Z component of the 3D cross product.
+	pub fn cross(&self, rhs: Vec2d) -> f32
+	{
+		self.x * rhs.y - self.y * rhs.x
+	}
+
+	// Unit length copy, or zero if there is no direction.
+	pub fn normalize(&self) -> Vec2d
+	{
+		let mag = self.magnitude();
+		if mag > 0.0
+		{
+			return *self / mag;
+		}
+		return Vec2d::zero();
+	}
+
+	pub fn distance(&self, rhs: Vec2d) -> f32
+	{
+		(*self - rhs).magnitude()
+	}
+
+	pub fn lerp(&self, rhs: Vec2d, t: f32) -> Vec2d
+	{
+		*self + (rhs - *self) * t
+	}
+
+	// Rotate counter-clockwise by angle in radians.
+	pub fn rotate(&self, angle: f32) -> Vec2d
+	{
+		let (sin, cos) = angle.sin_cos();
+		Vec2d::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
+	}
+
+	pub fn perpendicular(&self) -> Vec2d
+	{
+		Vec2d::new(-self.y, self.x)
+	}
+
+	pub fn angle(&self) -> f32
+	{
+		self.y.atan2(self.x)
+	}
+}
+
+impl Neg for Vec2d
+{
+	type Output = Vec2d;
+	fn neg(self) -> Vec2d
+	{
+		Vec2d
+		{
+			x: -self.x,
+			y: -self.y,
+		}
+	}
 }
 
 impl Add<Vec2d> for Vec2d
@@ -129,3 +207,476 @@ impl Div<f32> for Vec2d
 		}
 	}
 }
+
+impl AddAssign<Vec2d> for Vec2d
+{
+	fn add_assign(&mut self, rhs: Vec2d)
+	{
+		self.x += rhs.x;
+		self.y += rhs.y;
+	}
+}
+
+impl SubAssign<Vec2d> for Vec2d
+{
+	fn sub_assign(&mut self, rhs: Vec2d)
+	{
+		self.x -= rhs.x;
+		self.y -= rhs.y;
+	}
+}
+
+impl MulAssign<Vec2d> for Vec2d
+{
+	fn mul_assign(&mut self, rhs: Vec2d)
+	{
+		self.x *= rhs.x;
+		self.y *= rhs.y;
+	}
+}
+
+impl MulAssign<f32> for Vec2d
+{
+	fn mul_assign(&mut self, rhs: f32)
+	{
+		self.x *= rhs;
+		self.y *= rhs;
+	}
+}
+
+impl DivAssign<Vec2d> for Vec2d
+{
+	fn div_assign(&mut self, rhs: Vec2d)
+	{
+		self.x /= rhs.x;
+		self.y /= rhs.y;
+	}
+}
+
+impl DivAssign<f32> for Vec2d
+{
+	fn div_assign(&mut self, rhs: f32)
+	{
+		self.x /= rhs;
+		self.y /= rhs;
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// Mat3
+// Row-major 2D affine transform, applied to column vectors.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mat3
+{
+	pub m: [[f32; 3]; 3],
+}
+
+impl Mat3
+{
+	pub fn identity() -> Mat3
+	{
+		Mat3
+		{
+			m:
+			[
+				[1.0, 0.0, 0.0],
+				[0.0, 1.0, 0.0],
+				[0.0, 0.0, 1.0],
+			]
+		}
+	}
+
+	pub fn translation(offset: Vec2d) -> Mat3
+	{
+		Mat3
+		{
+			m:
+			[
+				[1.0, 0.0, offset.x],
+				[0.0, 1.0, offset.y],
+				[0.0, 0.0, 1.0],
+			]
+		}
+	}
+
+	pub fn rotation(angle: f32) -> Mat3
+	{
+		let (sin, cos) = angle.sin_cos();
+		Mat3
+		{
+			m:
+			[
+				[cos, -sin, 0.0],
+				[sin, cos, 0.0],
+				[0.0, 0.0, 1.0],
+			]
+		}
+	}
+
+	pub fn scale(scale: Vec2d) -> Mat3
+	{
+		Mat3
+		{
+			m:
+			[
+				[scale.x, 0.0, 0.0],
+				[0.0, scale.y, 0.0],
+				[0.0, 0.0, 1.0],
+			]
+		}
+	}
+
+	// Skew by tangent factors along x and y.
+	pub fn skew(skew: Vec2d) -> Mat3
+	{
+		Mat3
+		{
+			m:
+			[
+				[1.0, skew.x, 0.0],
+				[skew.y, 1.0, 0.0],
+				[0.0, 0.0, 1.0],
+			]
+		}
+	}
+
+	pub fn transform_point(&self, point: Vec2d) -> Vec2d
+	{
+		Vec2d::new(
+			self.m[0][0] * point.x + self.m[0][1] * point.y + self.m[0][2],
+			self.m[1][0] * point.x + self.m[1][1] * point.y + self.m[1][2])
+	}
+
+	// Transform without translation.
+	pub fn transform_vector(&self, vector: Vec2d) -> Vec2d
+	{
+		Vec2d::new(
+			self.m[0][0] * vector.x + self.m[0][1] * vector.y,
+			self.m[1][0] * vector.x + self.m[1][1] * vector.y)
+	}
+
+	pub fn determinant(&self) -> f32
+	{
+		self.m[0][0] * self.m[1][1] - self.m[0][1] * self.m[1][0]
+	}
+
+	// Inverse of the affine transform, or None if it is degenerate.
+	pub fn inverse(&self) -> Option<Mat3>
+	{
+		let det = self.determinant();
+		if det.abs() < 1e-8
+		{
+			return None;
+		}
+
+		let inv_det = 1.0 / det;
+		let a = self.m[1][1] * inv_det;
+		let b = -self.m[0][1] * inv_det;
+		let c = -self.m[1][0] * inv_det;
+		let d = self.m[0][0] * inv_det;
+		let tx = self.m[0][2];
+		let ty = self.m[1][2];
+		Some(Mat3
+		{
+			m:
+			[
+				[a, b, -(a * tx + b * ty)],
+				[c, d, -(c * tx + d * ty)],
+				[0.0, 0.0, 1.0],
+			]
+		})
+	}
+}
+
+impl Mul<Mat3> for Mat3
+{
+	type Output = Mat3;
+	fn mul(self, rhs: Mat3) -> Mat3
+	{
+		let mut out = Mat3 { m: [[0.0; 3]; 3] };
+		for row in 0..3
+		{
+			for col in 0..3
+			{
+				for idx in 0..3
+				{
+					out.m[row][col] += self.m[row][idx] * rhs.m[idx][col];
+				}
+			}
+		}
+		out
+	}
+}
+
+impl Mul<Vec2d> for Mat3
+{
+	type Output = Vec2d;
+	fn mul(self, rhs: Vec2d) -> Vec2d
+	{
+		self.transform_point(rhs)
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// Bounds
+// Axis aligned box.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bounds
+{
+	pub min: Vec2d,
+	pub max: Vec2d,
+}
+
+impl Bounds
+{
+	pub fn new(in_min: Vec2d, in_max: Vec2d) -> Bounds
+	{
+		Bounds
+		{
+			min: in_min,
+			max: in_max,
+		}
+	}
+
+	pub fn from_center(center: Vec2d, half_size: Vec2d) -> Bounds
+	{
+		Bounds::new(center - half_size, center + half_size)
+	}
+
+	pub fn size(&self) -> Vec2d
+	{
+		self.max - self.min
+	}
+
+	pub fn center(&self) -> Vec2d
+	{
+		(self.min + self.max) * 0.5
+	}
+
+	pub fn contains(&self, point: Vec2d) -> bool
+	{
+		point.x >= self.min.x && point.x <= self.max.x && point.y >= self.min.y && point.y <= self.max.y
+	}
+
+	pub fn intersects(&self, rhs: &Bounds) -> bool
+	{
+		self.min.x <= rhs.max.x && self.max.x >= rhs.min.x && self.min.y <= rhs.max.y && self.max.y >= rhs.min.y
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// Circle
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Circle
+{
+	pub center: Vec2d,
+	pub radius: f32,
+}
+
+impl Circle
+{
+	pub fn new(in_center: Vec2d, in_radius: f32) -> Circle
+	{
+		Circle
+		{
+			center: in_center,
+			radius: in_radius,
+		}
+	}
+
+	pub fn contains(&self, point: Vec2d) -> bool
+	{
+		(point - self.center).magnitude_squared() < self.radius * self.radius
+	}
+
+	pub fn intersects(&self, rhs: &Circle) -> bool
+	{
+		let radii = self.radius + rhs.radius;
+		(rhs.center - self.center).magnitude_squared() < radii * radii
+	}
+
+	pub fn bounds(&self) -> Bounds
+	{
+		Bounds::from_center(self.center, Vec2d::new(self.radius, self.radius))
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// Transform
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform
+{
+	pub position: Vec2d,
+	// Rotation in radians.
+	pub rotation: f32,
+	pub scale: Vec2d,
+	pub skew: Vec2d,
+}
+
+impl Transform
+{
+	pub fn new(in_position: Vec2d) -> Transform
+	{
+		Transform
+		{
+			position: in_position,
+			rotation: 0.0,
+			scale: Vec2d::new(1.0, 1.0),
+			skew: Vec2d::zero(),
+		}
+	}
+
+	// Scale, then skew, then rotate, then translate.
+	pub fn to_matrix(&self) -> Mat3
+	{
+		Mat3::translation(self.position) * Mat3::rotation(self.rotation) * Mat3::skew(self.skew) * Mat3::scale(self.scale)
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::f32::consts::PI;
+
+	fn assert_near(a: Vec2d, b: Vec2d)
+	{
+		assert!(a.distance(b) < 1e-4, "{:?} != {:?}", a, b);
+	}
+
+	fn assert_mat_near(a: Mat3, b: Mat3)
+	{
+		for row in 0..3
+		{
+			for col in 0..3
+			{
+				assert!((a.m[row][col] - b.m[row][col]).abs() < 1e-4, "{:?} != {:?}", a, b);
+			}
+		}
+	}
+
+	// Transform applied step by step, as documented for to_matrix.
+	fn translate_rotate_skew_scale(transform: &Transform, point: Vec2d) -> Vec2d
+	{
+		let scaled = point * transform.scale;
+		let skewed = Vec2d::new(scaled.x + transform.skew.x * scaled.y, scaled.y + transform.skew.y * scaled.x);
+		skewed.rotate(transform.rotation) + transform.position
+	}
+
+	#[test]
+	fn vec2d_ops()
+	{
+		let a = Vec2d::new(3.0, 4.0);
+		let b = Vec2d::new(1.0, -2.0);
+		assert_eq!(a + b, Vec2d::new(4.0, 2.0));
+		assert_eq!(a - b, Vec2d::new(2.0, 6.0));
+		assert_eq!(a * b, Vec2d::new(3.0, -8.0));
+		assert_eq!(a / Vec2d::new(3.0, 2.0), Vec2d::new(1.0, 2.0));
+		assert_eq!(a * 2.0, Vec2d::new(6.0, 8.0));
+		assert_eq!(a / 2.0, Vec2d::new(1.5, 2.0));
+		assert_eq!(a + 1.0, Vec2d::new(4.0, 5.0));
+		assert_eq!(a - 1.0, Vec2d::new(2.0, 3.0));
+		assert_eq!(-a, Vec2d::new(-3.0, -4.0));
+
+		let mut c = a;
+		c += b;
+		c -= Vec2d::new(1.0, 1.0);
+		c *= 2.0;
+		c /= Vec2d::new(2.0, 1.0);
+		assert_eq!(c, Vec2d::new(3.0, 2.0));
+
+		assert_eq!(a.magnitude(), 5.0);
+		assert_eq!(a.magnitude_squared(), 25.0);
+		assert_eq!(a.dot(b), -5.0);
+		assert_eq!(a.cross(b), -10.0);
+		assert_eq!(a.distance(b), (4.0f32 + 36.0).sqrt());
+		assert_near(a.normalize(), Vec2d::new(0.6, 0.8));
+		assert_eq!(Vec2d::zero().normalize(), Vec2d::zero());
+		assert_eq!(a.lerp(b, 0.5), Vec2d::new(2.0, 1.0));
+		assert_eq!(a.perpendicular(), Vec2d::new(-4.0, 3.0));
+		assert_near(Vec2d::new(1.0, 0.0).rotate(PI * 0.5), Vec2d::new(0.0, 1.0));
+		assert_near(Vec2d::from_angle(PI), Vec2d::new(-1.0, 0.0));
+		assert!((Vec2d::new(0.0, 2.0).angle() - PI * 0.5).abs() < 1e-6);
+	}
+
+	#[test]
+	fn mat3_compose()
+	{
+		let point = Vec2d::new(1.0, 2.0);
+		let translate = Mat3::translation(Vec2d::new(10.0, 20.0));
+		let rotate = Mat3::rotation(PI * 0.5);
+		let scale = Mat3::scale(Vec2d::new(2.0, 3.0));
+
+		// The right hand matrix applies first.
+		assert_near((translate * rotate) * point, Vec2d::new(8.0, 21.0));
+		assert_near((rotate * translate) * point, Vec2d::new(-22.0, 11.0));
+		assert_near(translate * (rotate * (scale * point)), (translate * rotate * scale) * point);
+		assert_near(Mat3::skew(Vec2d::new(1.0, 0.0)) * point, Vec2d::new(3.0, 2.0));
+		assert_near(translate.transform_vector(point), point);
+		assert_mat_near(Mat3::identity() * rotate, rotate);
+
+		let transform = Transform
+		{
+			position: Vec2d::new(5.0, -5.0),
+			rotation: 0.3,
+			scale: Vec2d::new(2.0, 0.5),
+			skew: Vec2d::new(0.2, -0.1),
+		};
+		let expected = translate_rotate_skew_scale(&transform, point);
+		assert_near(transform.to_matrix() * point, expected);
+	}
+
+	#[test]
+	fn mat3_inverse()
+	{
+		let matrices = [
+			Mat3::identity(),
+			Mat3::translation(Vec2d::new(-3.0, 7.0)),
+			Mat3::rotation(1.2) * Mat3::scale(Vec2d::new(2.0, 0.25)),
+			Mat3::translation(Vec2d::new(4.0, 1.0)) * Mat3::rotation(-0.7) * Mat3::skew(Vec2d::new(0.3, 0.1)) * Mat3::scale(Vec2d::new(1.5, 3.0)),
+		];
+		for matrix in matrices.iter()
+		{
+			let inverse = matrix.inverse().unwrap();
+			assert_mat_near(*matrix * inverse, Mat3::identity());
+			assert_mat_near(inverse * *matrix, Mat3::identity());
+		}
+		assert!(Mat3::scale(Vec2d::new(1.0, 0.0)).inverse().is_none());
+	}
+
+	#[test]
+	fn bounds_contains_and_intersects()
+	{
+		let bounds = Bounds::new(Vec2d::new(0.0, 0.0), Vec2d::new(4.0, 2.0));
+		assert_eq!(bounds.size(), Vec2d::new(4.0, 2.0));
+		assert_eq!(bounds.center(), Vec2d::new(2.0, 1.0));
+		assert_eq!(Bounds::from_center(Vec2d::new(2.0, 1.0), Vec2d::new(2.0, 1.0)), bounds);
+
+		assert!(bounds.contains(Vec2d::new(1.0, 1.0)));
+		assert!(bounds.contains(Vec2d::new(4.0, 2.0)));
+		assert!(!bounds.contains(Vec2d::new(4.1, 1.0)));
+		assert!(!bounds.contains(Vec2d::new(1.0, -0.1)));
+
+		assert!(bounds.intersects(&Bounds::new(Vec2d::new(3.0, 1.0), Vec2d::new(5.0, 5.0))));
+		assert!(bounds.intersects(&Bounds::new(Vec2d::new(1.0, 0.5), Vec2d::new(2.0, 1.5))));
+		assert!(bounds.intersects(&Bounds::new(Vec2d::new(4.0, 2.0), Vec2d::new(5.0, 3.0))));
+		assert!(!bounds.intersects(&Bounds::new(Vec2d::new(5.0, 0.0), Vec2d::new(6.0, 2.0))));
+		assert!(!bounds.intersects(&Bounds::new(Vec2d::new(0.0, 3.0), Vec2d::new(4.0, 4.0))));
+	}
+
+	#[test]
+	fn circle_contains_and_intersects()
+	{
+		let circle = Circle::new(Vec2d::new(1.0, 1.0), 2.0);
+		assert!(circle.contains(Vec2d::new(1.0, 1.0)));
+		assert!(circle.contains(Vec2d::new(2.0, 2.0)));
+		assert!(!circle.contains(Vec2d::new(3.0, 3.0)));
+		assert!(!circle.contains(Vec2d::new(3.0, 1.0)));
+
+		assert!(circle.intersects(&Circle::new(Vec2d::new(4.0, 1.0), 1.5)));
+		assert!(!circle.intersects(&Circle::new(Vec2d::new(4.0, 1.0), 1.0)));
+		assert!(!circle.intersects(&Circle::new(Vec2d::new(5.0, 5.0), 1.0)));
+
+		assert_eq!(circle.bounds(), Bounds::new(Vec2d::new(-1.0, -1.0), Vec2d::new(3.0, 3.0)));
+	}
+}
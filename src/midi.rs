@@ -0,0 +1,288 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use midir::{MidiIO, MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
+#[cfg(unix)]
+use midir::os::unix::{VirtualInput, VirtualOutput};
+use config::MidiPort;
+use input::{InputEvent, InputSource};
+use mixer::MixerChannel;
+use pitch;
+
+// Name other programs see for the game's ports.
+const CLIENT_NAME: &'static str = "Oscillomatch";
+
+const NOTE_OFF: u8 = 0x80;
+const NOTE_ON: u8 = 0x90;
+const PITCH_BEND: u8 = 0xe0;
+// Semitones either way of a full pitch bend, the General MIDI default.
+const BEND_RANGE: f32 = 2.0;
+
+// First port whose name contains name.
+fn find_port<T: MidiIO>(io: &T, name: &str) -> Result<T::Port, String>
+{
+	for port in io.ports()
+	{
+		if io.port_name(&port).map_or(false, |port_name| port_name.contains(name))
+		{
+			return Ok(port);
+		}
+	}
+	Err(format!("no MIDI port named '{}'", name))
+}
+
+#[cfg(unix)]
+fn create_virtual_output(output: MidiOutput) -> Result<MidiOutputConnection, String>
+{
+	output.create_virtual(CLIENT_NAME).map_err(|err| err.to_string())
+}
+
+#[cfg(not(unix))]
+fn create_virtual_output(_output: MidiOutput) -> Result<MidiOutputConnection, String>
+{
+	Err("virtual ports need Linux or macOS".to_string())
+}
+
+#[cfg(unix)]
+fn create_virtual_input<F>(input: MidiInput, callback: F) -> Result<MidiInputConnection<()>, String>
+	where F: FnMut(u64, &[u8], &mut ()) + Send + 'static
+{
+	input.create_virtual(CLIENT_NAME, callback, ()).map_err(|err| err.to_string())
+}
+
+#[cfg(not(unix))]
+fn create_virtual_input<F>(_input: MidiInput, _callback: F) -> Result<MidiInputConnection<()>, String>
+	where F: FnMut(u64, &[u8], &mut ()) + Send + 'static
+{
+	Err("virtual ports need Linux or macOS".to_string())
+}
+
+/////////////////////////////////////////////////////////////////////
+// MidiTones
+// Plays the mixer's tones as notes, one MIDI channel per waveform.
+// Tones between notes are reached with pitch bend.
+pub struct MidiTones
+{
+	connection: MidiOutputConnection,
+	// Latest frequency and volume of each waveform.
+	tones: [(f32, f32); 3],
+	// Note sounding on each channel.
+	playing: [Option<u8>; 3],
+	// Whether a send has failed, so it's only reported once.
+	send_failed: bool,
+}
+
+impl MidiTones
+{
+	// Connect to port, or None if MIDI output is off.
+	pub fn open(port: &MidiPort) -> Result<Option<MidiTones>, String>
+	{
+		if *port == MidiPort::None
+		{
+			return Ok(None);
+		}
+		let output = try!(MidiOutput::new(CLIENT_NAME).map_err(|err| err.to_string()));
+		let connection = match *port
+		{
+			MidiPort::Named(ref name) =>
+			{
+				let midi_port = try!(find_port(&output, name));
+				try!(output.connect(&midi_port, CLIENT_NAME).map_err(|err| err.to_string()))
+			},
+			_ => try!(create_virtual_output(output)),
+		};
+		Ok(Some(MidiTones
+		{
+			connection: connection,
+			tones: [(0.0, 0.0); 3],
+			playing: [None; 3],
+			send_failed: false,
+		}))
+	}
+
+	// Play on a thread of its own, fed by the returned sender. Sending MIDI
+	// can block, so it mustn't happen on the audio thread. The thread ends
+	// once every sender is dropped.
+	pub fn spawn(mut self) -> Sender<MixerChannel>
+	{
+		let (tx, rx) = channel();
+		thread::spawn(move ||
+		{
+			for message in rx.iter()
+			{
+				self.handle(&message);
+			}
+		});
+		tx
+	}
+
+	// Follow a message sent to the mixer.
+	pub fn handle(&mut self, message: &MixerChannel)
+	{
+		match *message
+		{
+			MixerChannel::Sine(f, v) => self.tones[0] = (f, v),
+			MixerChannel::Square(f, v) => self.tones[1] = (f, v),
+			MixerChannel::Sawtooth(f, v) => self.tones[2] = (f, v),
+			MixerChannel::Trigger => self.retrigger(),
+			_ => {},
+		}
+	}
+
+	fn stop(&mut self)
+	{
+		for idx in 0..self.playing.len()
+		{
+			if let Some(note) = self.playing[idx].take()
+			{
+				self.send(&[NOTE_OFF | idx as u8, note, 0]);
+			}
+		}
+	}
+
+	fn send(&mut self, message: &[u8])
+	{
+		if let Err(err) = self.connection.send(message)
+		{
+			if !self.send_failed
+			{
+				self.send_failed = true;
+				::print_warning(format!("Failed to send MIDI ({}), notes may be missing.", err));
+			}
+		}
+	}
+
+	// Restart notes for the current tones, as the mixer restarts its envelope.
+	fn retrigger(&mut self)
+	{
+		self.stop();
+		for idx in 0..self.tones.len()
+		{
+			let (freq, volume) = self.tones[idx];
+			if freq <= 0.0 || volume <= 0.0
+			{
+				continue;
+			}
+			let exact = pitch::freq_to_midi(freq);
+			let note = exact.round().max(0.0).min(127.0);
+			let bend = ((exact - note) / BEND_RANGE * 8192.0 + 8192.0).max(0.0).min(16383.0) as u16;
+			let velocity = (volume * 127.0).max(1.0).min(127.0) as u8;
+			self.send(&[PITCH_BEND | idx as u8, (bend & 0x7f) as u8, (bend >> 7) as u8]);
+			self.send(&[NOTE_ON | idx as u8, note as u8, velocity]);
+			self.playing[idx] = Some(note as u8);
+		}
+	}
+}
+
+impl Drop for MidiTones
+{
+	fn drop(&mut self)
+	{
+		self.stop();
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// MidiPads
+// Picks shapes with note on messages, the first shape at first_pad.
+pub struct MidiPads
+{
+	// Kept open while pads are read.
+	_connection: MidiInputConnection<()>,
+	// Notes pressed since the last poll.
+	rx: Receiver<u8>,
+	first_pad: i32,
+}
+
+impl MidiPads
+{
+	// Connect to port, or None if MIDI input is off.
+	pub fn open(port: &MidiPort, first_pad: i32) -> Result<Option<MidiPads>, String>
+	{
+		if *port == MidiPort::None
+		{
+			return Ok(None);
+		}
+		let input = try!(MidiInput::new(CLIENT_NAME).map_err(|err| err.to_string()));
+		let (tx, rx) = channel();
+		let callback = move |_time: u64, message: &[u8], _: &mut ()|
+		{
+			// Some controllers send note on with no velocity to release.
+			if message.len() >= 3 && message[0] & 0xf0 == NOTE_ON && message[2] > 0
+			{
+				// The receiver lives as long as the connection, so this can't fail.
+				let _ = tx.send(message[1]);
+			}
+		};
+		let connection = match *port
+		{
+			MidiPort::Named(ref name) =>
+			{
+				let midi_port = try!(find_port(&input, name));
+				try!(input.connect(&midi_port, CLIENT_NAME, callback, ()).map_err(|err| err.to_string()))
+			},
+			_ => try!(create_virtual_input(input, callback)),
+		};
+		Ok(Some(MidiPads
+		{
+			_connection: connection,
+			rx: rx,
+			first_pad: first_pad,
+		}))
+	}
+}
+
+impl InputSource for MidiPads
+{
+	fn poll(&mut self, events: &mut Vec<InputEvent>)
+	{
+		while let Ok(note) = self.rx.try_recv()
+		{
+			let idx = note as i32 - self.first_pad;
+			if idx >= 0
+			{
+				events.push(InputEvent::Pick(idx as usize));
+			}
+		}
+	}
+}
+
+#[cfg(all(test, feature = "midi", target_os = "linux"))]
+mod tests
+{
+	use std::thread;
+	use std::time::Duration;
+	use super::*;
+
+	// Needs the ALSA sequencer, which build machines often lack, so run
+	// it with: cargo test --features midi -- --ignored
+	#[test]
+	#[ignore]
+	fn note_on_from_virtual_port_picks()
+	{
+		let first_pad = 36;
+		let mut pads = MidiPads::open(&MidiPort::Virtual, first_pad).unwrap().unwrap();
+
+		// Play the pads from another client, as a controller would.
+		let output = MidiOutput::new("Oscillomatch test").unwrap();
+		let port = find_port(&output, CLIENT_NAME).unwrap();
+		let mut connection = output.connect(&port, "Oscillomatch test").unwrap();
+		connection.send(&[NOTE_ON, first_pad as u8 + 2, 100]).unwrap();
+		// Note on with no velocity is a release, and below first_pad isn't a pad.
+		connection.send(&[NOTE_ON, first_pad as u8 + 1, 0]).unwrap();
+		connection.send(&[NOTE_ON, first_pad as u8 - 1, 100]).unwrap();
+
+		let mut events = Vec::new();
+		for _ in 0..100
+		{
+			pads.poll(&mut events);
+			if !events.is_empty()
+			{
+				break;
+			}
+			thread::sleep(Duration::from_millis(10));
+		}
+		thread::sleep(Duration::from_millis(50));
+		pads.poll(&mut events);
+		assert_eq!(events, vec![InputEvent::Pick(2)]);
+	}
+}
@@ -0,0 +1,325 @@
+use std::f32::consts::PI;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use scope::ScopeBuffer;
+use sfx::{Sfx, Voice, MAX_VOICES};
+use music::Sequencer;
+
+/////////////////////////////////////////////////////////////////////
+// waves
+pub fn sine_wave(x: f32) -> f32
+{
+	return (x * PI * 2.0).sin();
+}
+
+pub fn square_wave(x: f32) -> f32
+{
+	let mod_x = (x * 2.0) % 2.0;
+	if mod_x > 1.0
+	{
+		return -1.0;
+	}
+	return 1.0;
+}
+
+pub fn sawtooth_wave(x: f32) -> f32
+{
+	return (x % 1.0) * 2.0 - 1.0;
+}
+
+pub fn envelope(x: f32, factor: f32) -> f32
+{
+	let mod_x = x % 1.0;
+	return (factor * mod_x * (PI / (1.0 + (factor - 1.0) * mod_x))).sin();
+}
+
+/////////////////////////////////////////////////////////////////////
+// Mixer
+type MixerFunc = Fn(f32) -> f32;
+
+#[derive(Copy, Clone, Debug)]
+pub enum MixerChannel
+{
+	Sine(f32, f32),
+	Square(f32, f32),
+	Sawtooth(f32, f32),
+	// Play a sound from the SFX bank over whatever else is playing.
+	Sfx(Sfx),
+	Trigger,
+	Pan(usize, f32),
+	Volume(Bus, f32),
+	// How busy the background music is, from 0 to 1.
+	MusicIntensity(f32),
+}
+
+// Volume groups the mixer scales separately.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Bus
+{
+	Master,
+	// Target and preview tones.
+	Tone,
+	// Feedback and menu sounds from the SFX bank.
+	Sfx,
+	// Background music.
+	Music,
+}
+
+pub const NUM_BUSES: usize = 4;
+
+#[derive(Copy, Clone)]
+pub struct MixerChannelParams
+{
+	pub phase_inc: f32,
+	pub phase: f32,
+	pub volume: f32,
+	// Stereo position, -1 is left and 1 is right.
+	pub pan: f32,
+}
+
+impl MixerChannelParams
+{
+	pub fn default() -> MixerChannelParams
+	{
+		MixerChannelParams
+		{
+			 phase_inc: 0.0,
+			 phase: 0.0,
+			 volume: 0.0,
+			 pan: 0.0,
+		}
+	}
+}
+
+// Synthesises the game's sound from messages sent over a channel. An
+// AudioBackend decides when it runs and where the output goes.
+pub struct Mixer
+{
+	freq: f32,
+	// Number of interleaved output channels.
+	num_channels: usize,
+	rx: Receiver<MixerChannel>,
+	channels: [MixerChannelParams; 3],
+	channel_targets: [MixerChannelParams; 3],
+	// Sound effects still playing.
+	voices: Vec<Voice>,
+	music: Sequencer,
+	// Told of every message as the mixer applies it.
+	listener: Option<Box<FnMut(&MixerChannel) + Send>>,
+	// Output shared with the scope display.
+	scope: Arc<Mutex<ScopeBuffer>>,
+	// Volume of each bus from 0 to 1.
+	bus_volumes: [f32; NUM_BUSES],
+
+	time: f32,
+}
+
+impl Mixer
+{
+	pub fn new(in_freq: i32, in_rx: Receiver<MixerChannel>, in_scope: Arc<Mutex<ScopeBuffer>>, in_bus_volumes: [f32; NUM_BUSES]) -> Mixer
+	{
+		Mixer
+		{
+			freq: in_freq as f32,
+			num_channels: 2,
+			rx: in_rx,
+			scope: in_scope,
+			bus_volumes: in_bus_volumes,
+			channels:
+			[
+				MixerChannelParams::default(),
+				MixerChannelParams::default(),
+				MixerChannelParams::default(),
+			],
+			channel_targets:
+			[
+				MixerChannelParams::default(),
+				MixerChannelParams::default(),
+				MixerChannelParams::default(),
+			],
+			voices: Vec::with_capacity(MAX_VOICES),
+			music: Sequencer::new(),
+			listener: None,
+			time: 0.0,
+		}
+	}
+
+	// Output format, once the backend knows what it got.
+	pub fn set_format(&mut self, freq: i32, num_channels: usize)
+	{
+		self.freq = freq as f32;
+		self.num_channels = num_channels;
+		self.scope.lock().unwrap().sample_rate = freq as f32;
+	}
+
+	pub fn freq(&self) -> i32
+	{
+		self.freq as i32
+	}
+
+	pub fn num_channels(&self) -> usize
+	{
+		self.num_channels
+	}
+
+	// Pass messages on to listener too, in step with the sound.
+	pub fn set_listener<F: FnMut(&MixerChannel) + Send + 'static>(&mut self, listener: F)
+	{
+		self.listener = Some(Box::new(listener));
+	}
+
+	// Apply pending messages, and jump straight to the tones they set
+	// rather than gliding there, as when rendering a tone on its own.
+	pub fn settle(&mut self)
+	{
+		self.receive();
+		for idx in 0..self.channels.len()
+		{
+			self.channels[idx].phase_inc = self.channel_targets[idx].phase_inc;
+			self.channels[idx].volume = self.channel_targets[idx].volume;
+		}
+	}
+
+	fn receive(&mut self)
+	{
+		'running: loop
+		{
+			let result = self.rx.try_recv();
+			match result
+			{
+				Ok(channel) => 
+				{
+					if let Some(ref mut listener) = self.listener
+					{
+						listener(&channel);
+					}
+					match channel
+					{
+						MixerChannel::Sine(f, v) =>
+						{
+							self.channel_targets[0].phase_inc = f / self.freq;
+							self.channel_targets[0].volume = v;
+						},
+						MixerChannel::Square(f, v) =>
+						{
+							self.channel_targets[1].phase_inc = f / self.freq;
+							self.channel_targets[1].volume = v;
+						},
+						MixerChannel::Sawtooth(f, v) =>
+						{
+							self.channel_targets[2].volume = v;
+							self.channel_targets[2].phase_inc = f / self.freq;
+						},
+						MixerChannel::Sfx(sfx) =>
+						{
+							if self.voices.len() >= MAX_VOICES
+							{
+								let oldest = (0..self.voices.len()).fold(0, |oldest, idx| if self.voices[idx].time > self.voices[oldest].time { idx } else { oldest });
+								self.voices.swap_remove(oldest);
+							}
+							self.voices.push(Voice::new(sfx));
+						},
+						MixerChannel::Trigger =>
+						{
+							// Restart the note envelope for the tone channels.
+							self.time = 0.0;
+							for idx in 0..self.channels.len()
+							{
+								self.channels[idx].phase = 0.0;
+							}
+						},
+						MixerChannel::Pan(idx, pan) =>
+						{
+							self.channels[idx].pan = pan;
+							self.channel_targets[idx].pan = pan;
+						},
+						MixerChannel::Volume(bus, volume) =>
+						{
+							self.bus_volumes[bus as usize] = volume;
+						},
+						MixerChannel::MusicIntensity(intensity) =>
+						{
+							self.music.set_intensity(intensity);
+						}
+					}
+				}
+				Err(_) => break 'running,
+			}
+		}
+	}
+
+	// Apply pending messages, then fill out with interleaved samples.
+	pub fn mix(&mut self, out: &mut [f32])
+	{
+		self.receive();
+
+		// Frequencies the music keeps clear of, so the target stays easy to hear.
+		let mut tone_freqs = [0.0; 3];
+		for idx in 0..self.channels.len()
+		{
+			if self.channel_targets[idx].volume > 0.0
+			{
+				tone_freqs[idx] = self.channel_targets[idx].phase_inc * self.freq;
+			}
+		}
+
+		for frame in out.chunks_mut(self.num_channels)
+		{
+			let mut out_left = 0.0;
+			let mut out_right = 0.0;
+			let mut tone_level = 0.0;
+			for idx in 0..self.channels.len()
+			{
+				self.channels[idx].phase = (self.channels[idx].phase + self.channels[idx].phase_inc) % 1.0;
+
+				let sample = match idx
+				{
+					0 => sine_wave( self.channels[idx].phase ) * envelope(self.time, 8.0),
+					1 => square_wave( self.channels[idx].phase ) * envelope(self.time, 8.0),
+					2 => sawtooth_wave( self.channels[idx].phase ) * envelope(self.time, 8.0),
+					_ => 0.0,
+				};
+				let out_val = self.channels[idx].volume * self.bus_volumes[Bus::Tone as usize] * sample;
+				tone_level += self.channels[idx].volume * self.bus_volumes[Bus::Tone as usize] * envelope(self.time, 8.0);
+				out_left = out_left + out_val * (1.0 - self.channels[idx].pan).min(1.0);
+				out_right = out_right + out_val * (1.0 + self.channels[idx].pan).min(1.0);
+
+				// Blend to target.
+				self.channels[idx].phase_inc = self.channels[idx].phase_inc * 0.999 + self.channel_targets[idx].phase_inc * 0.001;
+				self.channels[idx].volume = self.channels[idx].volume * 0.999 + self.channel_targets[idx].volume * 0.001;
+			}
+
+			// Sound effects play centred.
+			let mut sfx = 0.0;
+			for voice in self.voices.iter_mut()
+			{
+				sfx += voice.sample(self.freq);
+			}
+			out_left += sfx * self.bus_volumes[Bus::Sfx as usize];
+			out_right += sfx * self.bus_volumes[Bus::Sfx as usize];
+
+			let music = self.music.sample(self.freq, &tone_freqs, tone_level) * self.bus_volumes[Bus::Music as usize];
+			out_left += music;
+			out_right += music;
+
+			if frame.len() >= 2
+			{
+				frame[0] = out_left * self.bus_volumes[Bus::Master as usize] / 4.0;
+				frame[1] = out_right * self.bus_volumes[Bus::Master as usize] / 4.0;
+			}
+			else
+			{
+				frame[0] = (out_left + out_right) * self.bus_volumes[Bus::Master as usize] / 8.0;
+			}
+			self.time = (self.time + 1.0 / self.freq) % 8.0;
+		}
+
+		self.voices.retain(|voice| !voice.is_finished());
+
+		// Don't block audio if the game is reading the scope.
+		if let Ok(mut scope) = self.scope.try_lock()
+		{
+			scope.write(out, self.num_channels);
+		}
+	}
+}
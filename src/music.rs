@@ -0,0 +1,286 @@
+use mixer::{sine_wave, square_wave, sawtooth_wave};
+use pitch;
+use puzzle;
+use random::XorShift;
+
+const STEPS_PER_BAR: usize = 16;
+// Beats per minute at no intensity and at full intensity.
+const MIN_TEMPO: f32 = 84.0;
+const MAX_TEMPO: f32 = 132.0;
+// Bars played in each key before moving to the next.
+const BARS_PER_KEY: usize = 8;
+// Bass root of each key in turn as MIDI notes, moving by fourths.
+const KEYS: [i32; 4] = [28, 33, 26, 31];
+// Minor pentatonic, in semitones above the root.
+const SCALE: [i32; 5] = [0, 3, 5, 7, 10];
+// Notes closer than this many octaves to a target frequency move an octave away.
+const AVOID_RANGE: f32 = 0.5;
+// Lowest note worth moving down to.
+const MIN_NOTE_FREQ: f32 = 30.0;
+// Music level left while the target tone is at its loudest.
+const DUCK_GAIN: f32 = 0.2;
+// Per sample smoothing of the duck as the tone rises and falls.
+const DUCK_ATTACK: f32 = 0.995;
+const DUCK_RELEASE: f32 = 0.99995;
+const MAX_MUSIC_VOICES: usize = 16;
+// Streak length that counts as full intensity.
+const MAX_INTENSITY_STREAK: i32 = 10;
+
+// Marks a silent step in a pattern.
+const REST: i32 = -1;
+
+#[derive(Copy, Clone, PartialEq)]
+enum MusicWave
+{
+	Sine,
+	Square,
+	Sawtooth,
+	Noise,
+}
+
+/////////////////////////////////////////////////////////////////////
+// Layer
+// One instrument of the arrangement, with a calm and a busy pattern of
+// scale degrees per step.
+struct Layer
+{
+	wave: MusicWave,
+	// Octaves above the key's bass root.
+	octave: i32,
+	// Seconds for a note to fall to about a third of its volume.
+	decay: f32,
+	volume: f32,
+	// Intensity before the layer joins in, and before it plays busy.
+	min_intensity: f32,
+	busy_intensity: f32,
+	patterns: [[i32; STEPS_PER_BAR]; 2],
+}
+
+const LAYERS: [Layer; 4] =
+[
+	// Bass.
+	Layer
+	{
+		wave: MusicWave::Sawtooth, octave: 0, decay: 0.18, volume: 0.35, min_intensity: 0.0, busy_intensity: 0.5,
+		patterns:
+		[
+			[0, REST, REST, REST, REST, REST, REST, REST, 0, REST, REST, REST, 3, REST, REST, REST],
+			[0, REST, 0, REST, REST, 0, REST, 2, 0, REST, 0, REST, 3, REST, 4, REST],
+		],
+	},
+	// Hats, where any degree is a hit.
+	Layer
+	{
+		wave: MusicWave::Noise, octave: 7, decay: 0.02, volume: 0.1, min_intensity: 0.2, busy_intensity: 0.7,
+		patterns:
+		[
+			[REST, REST, 0, REST, REST, REST, 0, REST, REST, REST, 0, REST, REST, REST, 0, REST],
+			[0, REST, 0, 0, 0, REST, 0, 0, 0, REST, 0, 0, 0, REST, 0, 0],
+		],
+	},
+	// Arpeggio.
+	Layer
+	{
+		wave: MusicWave::Square, octave: 4, decay: 0.09, volume: 0.08, min_intensity: 0.4, busy_intensity: 0.8,
+		patterns:
+		[
+			[0, REST, REST, 2, REST, REST, 4, REST, REST, 2, REST, REST, 5, REST, REST, REST],
+			[0, 2, 4, 5, 4, 2, 0, 2, 5, 4, 2, 4, 7, 5, 4, 2],
+		],
+	},
+	// Bell melody.
+	Layer
+	{
+		wave: MusicWave::Sine, octave: 5, decay: 0.35, volume: 0.12, min_intensity: 0.7, busy_intensity: 0.9,
+		patterns:
+		[
+			[4, REST, REST, REST, REST, REST, REST, REST, 3, REST, REST, REST, REST, REST, REST, REST],
+			[4, REST, REST, 5, REST, REST, 7, REST, 5, REST, REST, 4, REST, REST, 2, REST],
+		],
+	},
+];
+
+// How busy the music should be, from 0 to 1, for a level and the number
+// of correct picks in a row.
+pub fn intensity(level: u32, streak: i32) -> f32
+{
+	let streak = streak.max(0).min(MAX_INTENSITY_STREAK) as f32 / MAX_INTENSITY_STREAK as f32;
+	puzzle::difficulty(level) * 0.6 + streak * 0.4
+}
+
+/////////////////////////////////////////////////////////////////////
+// MusicVoice
+#[derive(Copy, Clone)]
+struct MusicVoice
+{
+	wave: MusicWave,
+	freq: f32,
+	decay: f32,
+	volume: f32,
+	time: f32,
+	phase: f32,
+}
+
+impl MusicVoice
+{
+	fn is_finished(&self) -> bool
+	{
+		self.time > self.decay * 6.0
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// Sequencer
+// Step sequencer run by the mixer, one sample at a time.
+pub struct Sequencer
+{
+	intensity: f32,
+	// Steps played since starting.
+	steps: usize,
+	// Seconds into the current step.
+	step_time: f32,
+	// Play the busy pattern for this bar regardless of intensity.
+	fill: bool,
+	voices: Vec<MusicVoice>,
+	// Current duck gain.
+	duck: f32,
+	// Random state for fills and hats.
+	rng: XorShift,
+}
+
+impl Sequencer
+{
+	pub fn new() -> Sequencer
+	{
+		Sequencer
+		{
+			intensity: 0.0,
+			steps: 0,
+			step_time: 1.0,
+			fill: false,
+			voices: Vec::with_capacity(MAX_MUSIC_VOICES),
+			duck: 1.0,
+			rng: XorShift::new(0x2545f491),
+		}
+	}
+
+	pub fn set_intensity(&mut self, intensity: f32)
+	{
+		self.intensity = intensity.max(0.0).min(1.0);
+	}
+
+	fn step_length(&self) -> f32
+	{
+		let tempo = MIN_TEMPO + (MAX_TEMPO - MIN_TEMPO) * self.intensity;
+		// Four steps to a beat.
+		60.0 / (tempo * 4.0)
+	}
+
+	// Start the notes of the next step.
+	fn play_step(&mut self, avoid: &[f32])
+	{
+		let step = self.steps % STEPS_PER_BAR;
+		let bar = self.steps / STEPS_PER_BAR;
+		if step == 0
+		{
+			// Every fourth bar may fill.
+			self.fill = bar % 4 == 3 && self.rng.next_f32() < 0.5 + self.intensity * 0.5;
+		}
+		let root = KEYS[(bar / BARS_PER_KEY) % KEYS.len()];
+		self.voices.retain(|voice| !voice.is_finished());
+
+		for layer in LAYERS.iter()
+		{
+			if self.intensity < layer.min_intensity
+			{
+				continue;
+			}
+			let busy = self.fill || self.intensity >= layer.busy_intensity;
+			let degree = layer.patterns[busy as usize][step];
+			if degree == REST
+			{
+				continue;
+			}
+
+			let note = root + 12 * layer.octave + SCALE[degree as usize % SCALE.len()] + 12 * (degree / SCALE.len() as i32);
+			let freq = if layer.wave == MusicWave::Noise { pitch::midi_to_freq(note as f32) } else { avoid_band(pitch::midi_to_freq(note as f32), avoid) };
+			if self.voices.len() >= MAX_MUSIC_VOICES
+			{
+				self.voices.remove(0);
+			}
+			// Vary hat loudness so they don't sound mechanical.
+			let accent = if layer.wave == MusicWave::Noise { 0.6 + self.rng.next_f32() * 0.4 } else { 1.0 };
+			self.voices.push(MusicVoice
+			{
+				wave: layer.wave,
+				freq: freq,
+				decay: layer.decay,
+				volume: layer.volume * accent,
+				time: 0.0,
+				phase: 0.0,
+			});
+		}
+		self.steps += 1;
+	}
+
+	// Next sample at sample rate freq. Notes avoid the frequencies in avoid,
+	// and the music ducks by tone_level, the loudness of the target tone.
+	pub fn sample(&mut self, freq: f32, avoid: &[f32], tone_level: f32) -> f32
+	{
+		let step_length = self.step_length();
+		if self.step_time >= step_length
+		{
+			self.step_time -= step_length;
+			self.play_step(avoid);
+		}
+		self.step_time += 1.0 / freq;
+
+		let mut out = 0.0;
+		for idx in 0..self.voices.len()
+		{
+			let voice = &mut self.voices[idx];
+			voice.phase = (voice.phase + voice.freq / freq) % 1.0;
+			let wave = match voice.wave
+			{
+				MusicWave::Sine => sine_wave(voice.phase),
+				MusicWave::Square => square_wave(voice.phase),
+				MusicWave::Sawtooth => sawtooth_wave(voice.phase),
+				MusicWave::Noise => self.rng.next_f32() * 2.0 - 1.0,
+			};
+			let attack = (voice.time / 0.005).min(1.0);
+			out += wave * attack * (-voice.time / voice.decay).exp() * voice.volume;
+			voice.time += 1.0 / freq;
+		}
+		let duck_target = 1.0 - (1.0 - DUCK_GAIN) * tone_level.max(0.0).min(1.0);
+		let smoothing = if duck_target < self.duck { DUCK_ATTACK } else { DUCK_RELEASE };
+		self.duck = self.duck * smoothing + duck_target * (1.0 - smoothing);
+		out * self.duck
+	}
+}
+
+// Move freq by octaves until it is clear of every frequency in avoid.
+fn avoid_band(freq: f32, avoid: &[f32]) -> f32
+{
+	let mut freq = freq;
+	for _ in 0..3
+	{
+		let clash = avoid.iter().find(|&&target| target > 0.0 && (freq / target).log2().abs() < AVOID_RANGE);
+		match clash
+		{
+			Some(&target) =>
+			{
+				// Go down unless that would be too low to hear.
+				if freq <= target && freq * 0.5 >= MIN_NOTE_FREQ
+				{
+					freq *= 0.5;
+				}
+				else
+				{
+					freq *= 2.0;
+				}
+			},
+			None => break,
+		}
+	}
+	return freq;
+}
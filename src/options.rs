@@ -0,0 +1,242 @@
+use sdl2::keyboard::Keycode;
+use sdl2::rect::Rect;
+use sdl2::render::{Renderer, BlendMode};
+use config::Config;
+use input::{Action, ACTIONS};
+use lines::LineBatch;
+use math::Vec2d;
+use theme::{Theme, with_alpha};
+use draw_string;
+
+// Step for each press of left or right on a slider.
+const SLIDER_STEP: f32 = 0.05;
+// Scanlines stronger than this hide the shapes.
+const MAX_SCANLINES: f32 = 0.5;
+
+/////////////////////////////////////////////////////////////////////
+// OptionsItem
+#[derive(Copy, Clone, PartialEq)]
+enum OptionsItem
+{
+	MasterVolume,
+	ToneVolume,
+	SfxVolume,
+	MusicVolume,
+	Fullscreen,
+	Scanlines,
+	Noise,
+	Theme,
+	Binding(Action),
+}
+
+fn items() -> Vec<OptionsItem>
+{
+	let mut items = vec![
+		OptionsItem::MasterVolume,
+		OptionsItem::ToneVolume,
+		OptionsItem::SfxVolume,
+		OptionsItem::MusicVolume,
+		OptionsItem::Fullscreen,
+		OptionsItem::Scanlines,
+		OptionsItem::Noise,
+		OptionsItem::Theme,
+	];
+	items.extend(ACTIONS.iter().map(|&action| OptionsItem::Binding(action)));
+	return items;
+}
+
+impl OptionsItem
+{
+	fn label(&self) -> String
+	{
+		match *self
+		{
+			OptionsItem::MasterVolume => "MASTER VOLUME".to_string(),
+			OptionsItem::ToneVolume => "TONE VOLUME".to_string(),
+			OptionsItem::SfxVolume => "SFX VOLUME".to_string(),
+			OptionsItem::MusicVolume => "MUSIC VOLUME".to_string(),
+			OptionsItem::Fullscreen => "FULLSCREEN".to_string(),
+			OptionsItem::Scanlines => "SCANLINES".to_string(),
+			OptionsItem::Noise => "NOISE".to_string(),
+			OptionsItem::Theme => "THEME".to_string(),
+			OptionsItem::Binding(action) => format!("KEY {}", action.name().to_uppercase()),
+		}
+	}
+
+	// Slider value and its maximum, if this item is a slider.
+	fn slider(&self, config: &Config) -> Option<(f32, f32)>
+	{
+		match *self
+		{
+			OptionsItem::MasterVolume => Some((config.master_volume, 1.0)),
+			OptionsItem::ToneVolume => Some((config.tone_volume, 1.0)),
+			OptionsItem::SfxVolume => Some((config.sfx_volume, 1.0)),
+			OptionsItem::MusicVolume => Some((config.music_volume, 1.0)),
+			OptionsItem::Scanlines => Some((config.scanlines, MAX_SCANLINES)),
+			OptionsItem::Noise => Some((config.noise, 1.0)),
+			_ => None,
+		}
+	}
+
+	// Step a slider, or cycle a choice, in direction -1 or 1.
+	fn adjust(&self, config: &mut Config, direction: f32)
+	{
+		let step = |value: f32, max: f32| (((value + SLIDER_STEP * direction) / SLIDER_STEP).round() * SLIDER_STEP).max(0.0).min(max);
+		match *self
+		{
+			OptionsItem::MasterVolume => config.master_volume = step(config.master_volume, 1.0),
+			OptionsItem::ToneVolume => config.tone_volume = step(config.tone_volume, 1.0),
+			OptionsItem::SfxVolume => config.sfx_volume = step(config.sfx_volume, 1.0),
+			OptionsItem::MusicVolume => config.music_volume = step(config.music_volume, 1.0),
+			OptionsItem::Scanlines => config.scanlines = step(config.scanlines, MAX_SCANLINES),
+			OptionsItem::Noise => config.noise = step(config.noise, 1.0),
+			OptionsItem::Fullscreen => config.fullscreen = !config.fullscreen,
+			OptionsItem::Theme => config.theme = if direction < 0.0 { config.theme.previous() } else { config.theme.next() },
+			OptionsItem::Binding(..) => {},
+		}
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// OptionsMenu
+pub struct OptionsMenu
+{
+	pub is_open: bool,
+	// Highlighted item.
+	selected: usize,
+	// Waiting for a key to bind to the selected action.
+	rebinding: bool,
+}
+
+impl OptionsMenu
+{
+	pub fn new() -> OptionsMenu
+	{
+		OptionsMenu
+		{
+			is_open: false,
+			selected: 0,
+			rebinding: false,
+		}
+	}
+
+	pub fn open(&mut self)
+	{
+		self.is_open = true;
+		self.selected = 0;
+		self.rebinding = false;
+	}
+
+	// Handle a key while open, returning true if config changed.
+	pub fn handle_key(&mut self, key: Keycode, config: &mut Config) -> bool
+	{
+		let items = items();
+		let item = items[self.selected];
+
+		if self.rebinding
+		{
+			self.rebinding = false;
+			if let OptionsItem::Binding(action) = item
+			{
+				if key != Keycode::Escape
+				{
+					config.bindings.set(action, key);
+					return true;
+				}
+			}
+			return false;
+		}
+
+		if key == Keycode::Escape || config.bindings.action(key) == Some(Action::Options)
+		{
+			self.is_open = false;
+			return false;
+		}
+
+		match key
+		{
+			Keycode::Up => self.selected = (self.selected + items.len() - 1) % items.len(),
+			Keycode::Down => self.selected = (self.selected + 1) % items.len(),
+			Keycode::Left =>
+			{
+				item.adjust(config, -1.0);
+				return true;
+			},
+			Keycode::Right =>
+			{
+				item.adjust(config, 1.0);
+				return true;
+			},
+			Keycode::Return | Keycode::Space =>
+			{
+				if let OptionsItem::Binding(..) = item
+				{
+					self.rebinding = true;
+					return false;
+				}
+				item.adjust(config, 1.0);
+				return true;
+			},
+			_ => {},
+		}
+		return false;
+	}
+
+	// Draw over the game, which is dimmed if it can be.
+	pub fn draw(&self, renderer: &mut Renderer, lines: &mut LineBatch, config: &Config, theme: &Theme, screen_size: Vec2d) -> Result<(), String>
+	{
+		let dimmed = dim_background(renderer, theme, screen_size);
+
+		let items = items();
+		let row_height = (screen_size.y * 0.8 / (items.len() + 2) as f32).min(48.0);
+		let scale = row_height * 0.25;
+		let label_x = screen_size.x * 0.5 - scale * 24.0;
+		let value_x = screen_size.x * 0.5 + scale * 4.0;
+		let mut y = (screen_size.y - row_height * (items.len() + 2) as f32) * 0.5;
+
+		draw_string(renderer, lines, Vec2d::new(label_x, y), scale * 1.5, theme.high_score, &"OPTIONS".to_string());
+		y += row_height * 2.0;
+
+		for idx in 0..items.len()
+		{
+			let item = items[idx];
+			let color = if idx == self.selected { theme.shape_hover } else { theme.shape };
+			let label = if idx == self.selected { format!("> {}", item.label()) } else { item.label() };
+			draw_string(renderer, lines, Vec2d::new(label_x - scale * 3.0, y), scale, color, &label);
+
+			if let Some((value, max)) = item.slider(config)
+			{
+				let slider_width = scale * 16.0;
+				let start = Vec2d::new(value_x, y + scale);
+				lines.thickness = 1.0;
+				lines.add_line(start, start + Vec2d::new(slider_width, 0.0));
+				lines.flush(renderer, theme.shape);
+				lines.thickness = scale * 0.5;
+				lines.add_line(start, start + Vec2d::new(slider_width * value / max, 0.0));
+				lines.flush(renderer, color);
+			}
+			else
+			{
+				let value = match item
+				{
+					OptionsItem::Fullscreen => if config.fullscreen { "ON".to_string() } else { "OFF".to_string() },
+					OptionsItem::Theme => format!("< {} >", config.theme.name.to_uppercase()),
+					OptionsItem::Binding(..) if idx == self.selected && self.rebinding => "PRESS A KEY".to_string(),
+					OptionsItem::Binding(action) => config.bindings.key(action).name().to_uppercase(),
+					_ => String::new(),
+				};
+				draw_string(renderer, lines, Vec2d::new(value_x, y), scale, color, &value);
+			}
+			y += row_height;
+		}
+		return dimmed;
+	}
+}
+
+// Dim the game behind a screen drawn over it.
+pub fn dim_background(renderer: &mut Renderer, theme: &Theme, screen_size: Vec2d) -> Result<(), String>
+{
+	renderer.set_blend_mode(BlendMode::Blend);
+	renderer.set_draw_color(with_alpha(theme.background, 208));
+	renderer.fill_rect(Rect::new(0, 0, screen_size.x as u32, screen_size.y as u32))
+}
@@ -0,0 +1,208 @@
+use sdl2::pixels::Color;
+use sdl2::render::{Renderer, BlendMode};
+use std::f32::consts::PI;
+use rand::Rng;
+use math::*;
+use draw_line;
+use theme::color_components;
+
+/////////////////////////////////////////////////////////////////////
+// Emitter
+#[derive(Copy, Clone, PartialEq)]
+pub enum ParticleKind
+{
+	Point,
+	// Streak drawn back along the velocity.
+	Line,
+}
+
+// Describes how particles are spawned.
+#[derive(Copy, Clone)]
+pub struct Emitter
+{
+	pub kind: ParticleKind,
+	// Speed range in pixels per second.
+	pub min_speed: f32,
+	pub max_speed: f32,
+	// Spread around the emit direction in radians.
+	pub spread: f32,
+	// Lifetime range in seconds.
+	pub min_lifetime: f32,
+	pub max_lifetime: f32,
+	// Acceleration in pixels per second squared.
+	pub gravity: Vec2d,
+	// Damping rate per second, each update keeps 1 - drag * tick of the velocity.
+	pub drag: f32,
+	// Length of line particles in seconds of travel.
+	pub length: f32,
+	pub start_color: Color,
+	pub end_color: Color,
+}
+
+impl Emitter
+{
+	pub fn default() -> Emitter
+	{
+		Emitter
+		{
+			kind: ParticleKind::Point,
+			min_speed: 0.0,
+			max_speed: 0.0,
+			spread: 0.0,
+			min_lifetime: 1.0,
+			max_lifetime: 1.0,
+			gravity: Vec2d::zero(),
+			drag: 0.0,
+			length: 0.0,
+			start_color: Color::RGBA(255, 255, 255, 255),
+			end_color: Color::RGBA(255, 255, 255, 0),
+		}
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// Particle
+#[derive(Copy, Clone)]
+struct Particle
+{
+	position: Vec2d,
+	velocity: Vec2d,
+	age: f32,
+	lifetime: f32,
+	emitter: Emitter,
+}
+
+/////////////////////////////////////////////////////////////////////
+// ParticleSystem
+pub struct ParticleSystem
+{
+	// Fixed pool of particles, alive ones are listed in alive.
+	pool: Vec<Particle>,
+	// Indices of live particles in the pool.
+	alive: Vec<usize>,
+	// Indices of free particles in the pool.
+	free: Vec<usize>,
+}
+
+impl ParticleSystem
+{
+	pub fn new(capacity: usize) -> ParticleSystem
+	{
+		let particle = Particle
+		{
+			position: Vec2d::zero(),
+			velocity: Vec2d::zero(),
+			age: 0.0,
+			lifetime: 0.0,
+			emitter: Emitter::default(),
+		};
+		ParticleSystem
+		{
+			pool: vec![particle; capacity],
+			alive: Vec::with_capacity(capacity),
+			free: (0..capacity).rev().collect(),
+		}
+	}
+
+	pub fn num_alive(&self) -> usize
+	{
+		self.alive.len()
+	}
+
+	// Spawn a particle, dropping it if the pool is full.
+	pub fn spawn<R: Rng>(&mut self, rng: &mut R, emitter: &Emitter, position: Vec2d, direction: f32)
+	{
+		let idx = match self.free.pop()
+		{
+			Some(idx) => idx,
+			None => return,
+		};
+
+		let angle = direction + (rng.gen::<f32>() - 0.5) * emitter.spread;
+		let speed = emitter.min_speed + (emitter.max_speed - emitter.min_speed) * rng.gen::<f32>();
+		let particle = &mut self.pool[idx];
+		particle.position = position;
+		particle.velocity = Vec2d::from_angle(angle) * speed;
+		particle.age = 0.0;
+		particle.lifetime = emitter.min_lifetime + (emitter.max_lifetime - emitter.min_lifetime) * rng.gen::<f32>();
+		particle.emitter = *emitter;
+		self.alive.push(idx);
+	}
+
+	// Spawn count particles from one point in all directions.
+	pub fn burst<R: Rng>(&mut self, rng: &mut R, emitter: &Emitter, position: Vec2d, count: usize)
+	{
+		for _ in 0..count
+		{
+			let direction = rng.gen::<f32>() * PI * 2.0;
+			self.spawn(rng, emitter, position, direction);
+		}
+	}
+
+	// Spawn particles outwards along every step'th point of an outline.
+	pub fn burst_along<R: Rng>(&mut self, rng: &mut R, emitter: &Emitter, points: &[Vec2d], transform: &Mat3, step: usize)
+	{
+		let center = transform.transform_point(Vec2d::zero());
+		let mut idx = 0;
+		while idx < points.len()
+		{
+			let position = transform.transform_point(points[idx]);
+			let direction = (position - center).angle();
+			self.spawn(rng, emitter, position, direction);
+			idx += step.max(1);
+		}
+	}
+
+	pub fn update(&mut self, tick: f32)
+	{
+		let mut alive_idx = 0;
+		while alive_idx < self.alive.len()
+		{
+			let idx = self.alive[alive_idx];
+			let particle = &mut self.pool[idx];
+			particle.age += tick;
+			if particle.age >= particle.lifetime
+			{
+				self.alive.swap_remove(alive_idx);
+				self.free.push(idx);
+				continue;
+			}
+
+			particle.velocity += particle.emitter.gravity * tick;
+			particle.velocity *= (1.0 - particle.emitter.drag * tick).max(0.0);
+			particle.position += particle.velocity * tick;
+			alive_idx += 1;
+		}
+	}
+
+	pub fn draw(&self, renderer: &mut Renderer)
+	{
+		renderer.set_blend_mode(BlendMode::Blend);
+		for &idx in self.alive.iter()
+		{
+			let particle = &self.pool[idx];
+			let t = particle.age / particle.lifetime;
+			renderer.set_draw_color(lerp_color(particle.emitter.start_color, particle.emitter.end_color, t));
+			match particle.emitter.kind
+			{
+				ParticleKind::Point =>
+				{
+					renderer.draw_point(particle.position.get_point());
+				},
+				ParticleKind::Line =>
+				{
+					let tail = particle.position - particle.velocity * particle.emitter.length;
+					draw_line(renderer, tail, particle.position);
+				},
+			}
+		}
+	}
+}
+
+fn lerp_color(a: Color, b: Color, t: f32) -> Color
+{
+	let (ar, ag, ab, aa) = color_components(a);
+	let (br, bg, bb, ba) = color_components(b);
+	let lerp = |x: u8, y: u8| (x as f32 + (y as f32 - x as f32) * t) as u8;
+	Color::RGBA(lerp(ar, br), lerp(ag, bg), lerp(ab, bb), lerp(aa, ba))
+}
@@ -0,0 +1,410 @@
+/////////////////////////////////////////////////////////////////////
+// Pitch
+// MIDI note numbers, note names and tunings. Notes are MIDI numbers,
+// so middle C (C4) is 60 and A4 is 69.
+pub const A4_NOTE: i32 = 69;
+pub const A4_FREQ: f32 = 440.0;
+pub const CENTS_PER_OCTAVE: f32 = 1200.0;
+
+const NOTE_NAMES: [&'static str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+// Equal tempered frequency of a note, which may be fractional.
+pub fn midi_to_freq(note: f32) -> f32
+{
+	A4_FREQ * ((note - A4_NOTE as f32) / 12.0).exp2()
+}
+
+pub fn freq_to_midi(freq: f32) -> f32
+{
+	A4_NOTE as f32 + 12.0 * (freq / A4_FREQ).log2()
+}
+
+// Shift freq by a number of cents.
+pub fn detune(freq: f32, cents: f32) -> f32
+{
+	freq * (cents / CENTS_PER_OCTAVE).exp2()
+}
+
+// Cents from freq a up to freq b.
+pub fn cents_between(a: f32, b: f32) -> f32
+{
+	CENTS_PER_OCTAVE * (b / a).log2()
+}
+
+// Parse a note name such as "A4", "C#5", "Bb3" or "C-1".
+pub fn parse_note(name: &str) -> Option<i32>
+{
+	let mut chars = name.trim().chars();
+	let mut note = match chars.next().map(|c| c.to_ascii_uppercase())
+	{
+		Some('C') => 0,
+		Some('D') => 2,
+		Some('E') => 4,
+		Some('F') => 5,
+		Some('G') => 7,
+		Some('A') => 9,
+		Some('B') => 11,
+		_ => return None,
+	};
+	let rest = chars.as_str();
+	let octave = if rest.starts_with('#')
+	{
+		note += 1;
+		&rest[1..]
+	}
+	else if rest.starts_with('b')
+	{
+		note -= 1;
+		&rest[1..]
+	}
+	else
+	{
+		rest
+	};
+	match octave.parse::<i32>()
+	{
+		Ok(octave) if octave >= -1 && octave <= 9 => Some((octave + 1) * 12 + note),
+		_ => None,
+	}
+}
+
+// Name of a note using sharps, such as "C#5".
+pub fn note_name(note: i32) -> String
+{
+	let pitch_class = ((note % 12) + 12) % 12;
+	let octave = (note - pitch_class) / 12 - 1;
+	format!("{}{}", NOTE_NAMES[pitch_class as usize], octave)
+}
+
+/////////////////////////////////////////////////////////////////////
+// Tuning
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Temperament
+{
+	// Twelve equal semitones to the octave.
+	Equal,
+	// Five limit just intonation above the tonic.
+	Just,
+	// Stacked pure fifths above the tonic.
+	Pythagorean,
+}
+
+// Ratio above the tonic of each semitone of the scale.
+const JUST_RATIOS: [f32; 12] = [1.0, 16.0 / 15.0, 9.0 / 8.0, 6.0 / 5.0, 5.0 / 4.0, 4.0 / 3.0, 45.0 / 32.0, 3.0 / 2.0, 8.0 / 5.0, 5.0 / 3.0, 9.0 / 5.0, 15.0 / 8.0];
+const PYTHAGOREAN_RATIOS: [f32; 12] = [1.0, 256.0 / 243.0, 9.0 / 8.0, 32.0 / 27.0, 81.0 / 64.0, 4.0 / 3.0, 729.0 / 512.0, 3.0 / 2.0, 128.0 / 81.0, 27.0 / 16.0, 16.0 / 9.0, 243.0 / 128.0];
+
+#[derive(Copy, Clone, Debug)]
+pub struct Tuning
+{
+	pub temperament: Temperament,
+	// Note the scale is tuned from. The tonic itself is equal tempered.
+	pub tonic: i32,
+	// Frequency of A4 in Hz.
+	pub reference: f32,
+	// Offset applied to every note.
+	pub cents: f32,
+}
+
+impl Tuning
+{
+	pub fn equal() -> Tuning
+	{
+		Tuning
+		{
+			temperament: Temperament::Equal,
+			tonic: A4_NOTE,
+			reference: A4_FREQ,
+			cents: 0.0,
+		}
+	}
+
+	pub fn with_temperament(temperament: Temperament, tonic: i32) -> Tuning
+	{
+		Tuning
+		{
+			temperament: temperament,
+			tonic: tonic,
+			reference: A4_FREQ,
+			cents: 0.0,
+		}
+	}
+
+	pub fn freq(&self, note: i32) -> f32
+	{
+		let tonic_freq = self.reference * ((self.tonic - A4_NOTE) as f32 / 12.0).exp2();
+		let semitones = note - self.tonic;
+		let degree = ((semitones % 12) + 12) % 12;
+		let octave = (semitones - degree) / 12;
+		let ratio = match self.temperament
+		{
+			Temperament::Equal => (degree as f32 / 12.0).exp2(),
+			Temperament::Just => JUST_RATIOS[degree as usize],
+			Temperament::Pythagorean => PYTHAGOREAN_RATIOS[degree as usize],
+		};
+		detune(tonic_freq * (octave as f32).exp2() * ratio, self.cents)
+	}
+}
+
+impl Temperament
+{
+	// Name used in the config file.
+	pub fn name(&self) -> &'static str
+	{
+		match *self
+		{
+			Temperament::Equal => "equal",
+			Temperament::Just => "just",
+			Temperament::Pythagorean => "pythagorean",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Temperament>
+	{
+		match name
+		{
+			"equal" => Some(Temperament::Equal),
+			"just" => Some(Temperament::Just),
+			"pythagorean" => Some(Temperament::Pythagorean),
+			_ => None,
+		}
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// Interval
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Interval
+{
+	MinorSecond,
+	MajorSecond,
+	MinorThird,
+	MajorThird,
+	PerfectFourth,
+	Tritone,
+	PerfectFifth,
+	MinorSixth,
+	MajorSixth,
+	MinorSeventh,
+	MajorSeventh,
+	Octave,
+}
+
+// Roughly easiest to hear first.
+pub const INTERVALS: [Interval; 12] =
+[
+	Interval::Octave,
+	Interval::PerfectFifth,
+	Interval::PerfectFourth,
+	Interval::MajorThird,
+	Interval::MinorThird,
+	Interval::MajorSixth,
+	Interval::MinorSeventh,
+	Interval::MajorSecond,
+	Interval::Tritone,
+	Interval::MinorSixth,
+	Interval::MajorSeventh,
+	Interval::MinorSecond,
+];
+
+impl Interval
+{
+	pub fn semitones(&self) -> i32
+	{
+		*self as i32 + 1
+	}
+
+	pub fn name(&self) -> &'static str
+	{
+		match *self
+		{
+			Interval::MinorSecond => "minor second",
+			Interval::MajorSecond => "major second",
+			Interval::MinorThird => "minor third",
+			Interval::MajorThird => "major third",
+			Interval::PerfectFourth => "perfect fourth",
+			Interval::Tritone => "tritone",
+			Interval::PerfectFifth => "perfect fifth",
+			Interval::MinorSixth => "minor sixth",
+			Interval::MajorSixth => "major sixth",
+			Interval::MinorSeventh => "minor seventh",
+			Interval::MajorSeventh => "major seventh",
+			Interval::Octave => "octave",
+		}
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// Chord
+// Three note chords, one note for each mixer waveform.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Chord
+{
+	Major,
+	Minor,
+	Sus4,
+	Sus2,
+	Diminished,
+	Augmented,
+}
+
+// Roughly easiest to hear first.
+pub const CHORDS: [Chord; 6] = [Chord::Major, Chord::Minor, Chord::Sus4, Chord::Diminished, Chord::Augmented, Chord::Sus2];
+
+impl Chord
+{
+	// Semitones of each note above the root.
+	pub fn semitones(&self) -> [i32; 3]
+	{
+		match *self
+		{
+			Chord::Major => [0, 4, 7],
+			Chord::Minor => [0, 3, 7],
+			Chord::Sus4 => [0, 5, 7],
+			Chord::Sus2 => [0, 2, 7],
+			Chord::Diminished => [0, 3, 6],
+			Chord::Augmented => [0, 4, 8],
+		}
+	}
+
+	pub fn name(&self) -> &'static str
+	{
+		match *self
+		{
+			Chord::Major => "major",
+			Chord::Minor => "minor",
+			Chord::Sus4 => "sus4",
+			Chord::Sus2 => "sus2",
+			Chord::Diminished => "diminished",
+			Chord::Augmented => "augmented",
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn assert_freq(actual: f32, expected: f32)
+	{
+		assert!((actual - expected).abs() < expected * 1e-5, "{} != {}", actual, expected);
+	}
+
+	#[test]
+	fn parse_note_names()
+	{
+		assert_eq!(parse_note("A4"), Some(69));
+		assert_eq!(parse_note("C#5"), Some(73));
+		assert_eq!(parse_note("Bb3"), Some(58));
+		assert_eq!(parse_note("C-1"), Some(0));
+		assert_eq!(parse_note("G9"), Some(127));
+		assert_eq!(parse_note(" c4 "), Some(60));
+		assert_eq!(parse_note("H4"), None);
+		assert_eq!(parse_note("A"), None);
+		assert_eq!(parse_note("A10"), None);
+		assert_eq!(parse_note("C-2"), None);
+		assert_eq!(parse_note(""), None);
+	}
+
+	#[test]
+	fn note_names_round_trip()
+	{
+		assert_eq!(note_name(60), "C4");
+		assert_eq!(note_name(70), "A#4");
+		assert_eq!(note_name(0), "C-1");
+		for note in 0..128
+		{
+			assert_eq!(parse_note(&note_name(note)), Some(note));
+		}
+	}
+
+	#[test]
+	fn midi_and_freq_convert_both_ways()
+	{
+		assert_freq(midi_to_freq(69.0), 440.0);
+		assert_freq(midi_to_freq(57.0), 220.0);
+		assert_freq(midi_to_freq(60.0), 261.62558);
+		for note in 0..128
+		{
+			assert!((freq_to_midi(midi_to_freq(note as f32)) - note as f32).abs() < 1e-3);
+		}
+		assert_freq(detune(440.0, 1200.0), 880.0);
+		assert!((cents_between(440.0, 466.16376) - 100.0).abs() < 1e-2);
+	}
+
+	#[test]
+	fn equal_tuning_matches_midi()
+	{
+		let tuning = Tuning::equal();
+		for note in 21..109
+		{
+			assert_freq(tuning.freq(note), midi_to_freq(note as f32));
+		}
+
+		let tuning = Tuning { reference: 432.0, cents: 100.0, ..Tuning::equal() };
+		assert_freq(tuning.freq(69), detune(432.0, 100.0));
+		assert_freq(tuning.freq(57), detune(216.0, 100.0));
+	}
+
+	#[test]
+	fn just_tuning_uses_pure_ratios()
+	{
+		let tuning = Tuning::with_temperament(Temperament::Just, A4_NOTE);
+		assert_freq(tuning.freq(69), 440.0);
+		assert_freq(tuning.freq(73), 550.0);
+		assert_freq(tuning.freq(76), 660.0);
+		assert_freq(tuning.freq(81), 880.0);
+		// Below the tonic wraps to the octave under it.
+		assert_freq(tuning.freq(64), 330.0);
+		assert_freq(tuning.freq(60), 440.0 * 0.5 * 6.0 / 5.0);
+
+		// Tonic itself is equal tempered.
+		let tuning = Tuning::with_temperament(Temperament::Just, 60);
+		assert_freq(tuning.freq(60), midi_to_freq(60.0));
+		assert_freq(tuning.freq(67), midi_to_freq(60.0) * 1.5);
+	}
+
+	#[test]
+	fn pythagorean_tuning_stacks_fifths()
+	{
+		let tuning = Tuning::with_temperament(Temperament::Pythagorean, A4_NOTE);
+		assert_freq(tuning.freq(76), 660.0);
+		assert_freq(tuning.freq(73), 440.0 * 81.0 / 64.0);
+		assert_freq(tuning.freq(71), 440.0 * 9.0 / 8.0);
+		assert_freq(tuning.freq(57), 220.0);
+		// The Pythagorean major third is sharper than the just one.
+		let just = Tuning::with_temperament(Temperament::Just, A4_NOTE);
+		assert!((cents_between(just.freq(73), tuning.freq(73)) - 21.506).abs() < 0.01);
+	}
+
+	#[test]
+	fn temperament_names_round_trip()
+	{
+		for &temperament in [Temperament::Equal, Temperament::Just, Temperament::Pythagorean].iter()
+		{
+			assert_eq!(Temperament::from_name(temperament.name()), Some(temperament));
+		}
+		assert_eq!(Temperament::from_name("meantone"), None);
+	}
+
+	#[test]
+	fn intervals_and_chords()
+	{
+		assert_eq!(Interval::MinorSecond.semitones(), 1);
+		assert_eq!(Interval::PerfectFifth.semitones(), 7);
+		assert_eq!(Interval::Octave.semitones(), 12);
+		for a in 0..INTERVALS.len()
+		{
+			for b in 0..a
+			{
+				assert!(INTERVALS[a] != INTERVALS[b]);
+			}
+		}
+		for chord in CHORDS.iter()
+		{
+			let semitones = chord.semitones();
+			assert_eq!(semitones[0], 0);
+			assert!(semitones[1] < semitones[2]);
+		}
+	}
+}
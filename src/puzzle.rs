@@ -0,0 +1,879 @@
+use rand::{Rng, SeedableRng, StdRng};
+use mixer::MixerChannel;
+use pitch;
+use pitch::{Tuning, CHORDS, INTERVALS};
+
+/////////////////////////////////////////////////////////////////////
+// Tuning
+// Distinguishability of distractors at easiest and hardest difficulty.
+const EASY_DISTANCE: f32 = 1.5;
+const HARD_DISTANCE: f32 = 0.2;
+// Levels taken to get most of the way to hardest difficulty.
+const DIFFICULTY_SCALE: f32 = 25.0;
+
+// Perceptual weight per unit volume moved between waveforms.
+const TIMBRE_WEIGHT: f32 = 3.0;
+// Perceptual weight per semitone of detune.
+const PITCH_WEIGHT: f32 = 0.5;
+// Perceptual weight of changing a harmonic ratio.
+const RATIO_WEIGHT: f32 = 0.6;
+// Perceptual weight per unit volume change.
+const VOLUME_WEIGHT: f32 = 1.5;
+
+const HARMONIC_RATIOS: [f32; 6] = [1.0, 2.0, 1.5, 0.5, 3.0, 1.25];
+// A2 to A5.
+const MIN_FREQ: f32 = 110.0;
+const MAX_FREQ: f32 = 880.0;
+// Upper bounds of each frequency band, at A3 and A4.
+pub const BAND_LIMITS: [f32; 3] = [220.0, 440.0, ::std::f32::MAX];
+pub const NUM_BANDS: usize = 3;
+// Candidate pitches to pick between when favouring a band.
+const BAND_CANDIDATES: usize = 4;
+const MAX_MUTATIONS: usize = 8;
+const MAX_REROLLS: usize = 16;
+const MAX_ROUND_REROLLS: usize = 8;
+// Most candidates offered in a round.
+const MAX_CANDIDATES: usize = 8;
+
+// Frequency ratios for Lissajous figures, roughly easiest first.
+const LISSAJOUS_RATIOS: [(f32, f32); 8] = [(1.0, 1.0), (1.0, 2.0), (1.0, 3.0), (2.0, 3.0), (3.0, 4.0), (2.0, 5.0), (3.0, 5.0), (4.0, 5.0)];
+const LISSAJOUS_MIN_LEVEL: u32 = 10;
+const LISSAJOUS_CHANCE: f32 = 0.3;
+// Shapes plot one cycle per 55Hz, so multiples of it keep figures closed.
+const LISSAJOUS_BASE_FREQ: f32 = 55.0;
+
+const HARMONY_MIN_LEVEL: u32 = 15;
+const HARMONY_CHANCE: f32 = 0.25;
+// Difficulty before chords join intervals.
+const CHORD_MIN_DIFFICULTY: f32 = 0.4;
+// Roots of interval and chord rounds, A2 to A3.
+const HARMONY_MIN_ROOT: i32 = 45;
+const HARMONY_MAX_ROOT: i32 = 57;
+// Volume of the root, then of the notes above it.
+const HARMONY_VOLUMES: [f32; 3] = [0.5, 0.3, 0.3];
+
+// Sounds more similar than this are considered ambiguous.
+pub const MAX_SIMILARITY: f32 = 0.9;
+// Similarity weight per semitone of pitch difference, after octave folding.
+const SIMILARITY_PITCH_WEIGHT: f32 = 0.5;
+// Pitch difference in semitones that a whole octave counts as.
+const SIMILARITY_OCTAVE_SEMITONES: f32 = 0.25;
+// Similarity weight for difference in waveform mix.
+const SIMILARITY_TIMBRE_WEIGHT: f32 = 1.5;
+// Similarity weight for relative loudness difference.
+const SIMILARITY_LOUDNESS_WEIGHT: f32 = 0.5;
+
+/////////////////////////////////////////////////////////////////////
+// Sound
+#[derive(Copy, Clone, Debug)]
+pub struct Sound
+{
+	// Frequency of each waveform.
+	pub freqs: [f32; 3],
+	// Volume of each waveform.
+	pub volumes: [f32; 3],
+}
+
+impl Sound
+{
+	pub fn to_channels(&self) -> [MixerChannel; 3]
+	{
+		[
+			MixerChannel::Sine(self.freqs[0], self.volumes[0]),
+			MixerChannel::Square(self.freqs[1], self.volumes[1]),
+			MixerChannel::Sawtooth(self.freqs[2], self.volumes[2]),
+		]
+	}
+
+	pub fn from_channels(channels: &[MixerChannel; 3]) -> Sound
+	{
+		let mut sound = Sound
+		{
+			freqs: [0.0; 3],
+			volumes: [0.0; 3],
+		};
+		for channel in channels.iter()
+		{
+			let (idx, f, v) = match *channel
+			{
+				MixerChannel::Sine(f, v) => (0, f, v),
+				MixerChannel::Square(f, v) => (1, f, v),
+				MixerChannel::Sawtooth(f, v) => (2, f, v),
+				_ => continue,
+			};
+			sound.freqs[idx] = f;
+			sound.volumes[idx] = v;
+		}
+		return sound;
+	}
+
+	// Loudest waveform in the mix.
+	pub fn dominant_wave(&self) -> usize
+	{
+		let mut dominant = 0;
+		for idx in 1..3
+		{
+			if self.volumes[idx] > self.volumes[dominant]
+			{
+				dominant = idx;
+			}
+		}
+		return dominant;
+	}
+
+	pub fn band(&self) -> usize
+	{
+		band_of(self.freqs[self.dominant_wave()])
+	}
+
+	// Number of waveforms in the mix.
+	pub fn complexity(&self) -> usize
+	{
+		self.active().len()
+	}
+
+	// Waveforms driving x and y of a Lissajous figure.
+	pub fn lissajous_waves(&self) -> (usize, usize)
+	{
+		let active = self.active();
+		match active.len()
+		{
+			0 => (0, 1),
+			1 => (active[0], active[0]),
+			_ => (active[0], active[1]),
+		}
+	}
+
+	fn total_volume(&self) -> f32
+	{
+		self.volumes[0] + self.volumes[1] + self.volumes[2]
+	}
+
+	fn active(&self) -> Vec<usize>
+	{
+		(0..3).filter(|&idx| self.volumes[idx] > 0.0).collect()
+	}
+
+	fn root_freq(&self) -> f32
+	{
+		match self.active().first()
+		{
+			Some(&idx) => self.freqs[idx],
+			None => self.freqs[0],
+		}
+	}
+}
+
+pub fn band_of(freq: f32) -> usize
+{
+	BAND_LIMITS.iter().position(|&limit| freq < limit).unwrap_or(NUM_BANDS - 1)
+}
+
+/////////////////////////////////////////////////////////////////////
+// Bias
+// Weights used to favour the sounds and confusions a player struggles with.
+#[derive(Copy, Clone, Debug)]
+pub struct Bias
+{
+	// Weight for each waveform being dominant in the target.
+	pub waveforms: [f32; 3],
+	// Weight for each frequency band of the target.
+	pub bands: [f32; NUM_BANDS],
+	// Weight for targets mixing 1, 2 or 3 waveforms.
+	pub complexity: [f32; 3],
+	// Weight for shifting a distractor from one waveform towards another.
+	pub confusions: [[f32; 3]; 3],
+	// Only generate this family, or None to mix them by level.
+	pub family: Option<Family>,
+	// Tuning of interval and chord rounds.
+	pub tuning: Tuning,
+}
+
+impl Bias
+{
+	pub fn uniform() -> Bias
+	{
+		Bias
+		{
+			waveforms: [1.0; 3],
+			bands: [1.0; NUM_BANDS],
+			complexity: [1.0; 3],
+			confusions: [[1.0; 3]; 3],
+			family: None,
+			tuning: Tuning::equal(),
+		}
+	}
+}
+
+fn choose_weighted<R: Rng>(rng: &mut R, weights: &[f32]) -> usize
+{
+	let total: f32 = weights.iter().fold(0.0, |sum, &weight| sum + weight);
+	if total <= 0.0
+	{
+		return rng.gen_range(0, weights.len());
+	}
+
+	let mut pick = rng.gen::<f32>() * total;
+	for idx in 0..weights.len()
+	{
+		if pick < weights[idx]
+		{
+			return idx;
+		}
+		pick -= weights[idx];
+	}
+	return weights.len() - 1;
+}
+
+/////////////////////////////////////////////////////////////////////
+// Puzzle
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Family
+{
+	// Match the sound to a polar plot of its waveform mix.
+	Timbre,
+	// Match the stereo sound to a Lissajous figure.
+	Lissajous,
+	// Match the interval or chord heard to its shape.
+	Harmony,
+}
+
+pub struct Puzzle
+{
+	// Kind of puzzle.
+	pub family: Family,
+	// Phase offset between x and y of Lissajous figures.
+	pub phase: f32,
+	// Sound the player has to find.
+	pub target: Sound,
+	// Sounds that are not the target.
+	pub distractors: Vec<Sound>,
+	// Smallest distinguishability between target and a distractor.
+	pub distinguishability: f32,
+	// Seed the puzzle was generated from.
+	pub seed: u32,
+}
+
+/////////////////////////////////////////////////////////////////////
+// Similarity
+// Similarity of two channel sets, from 0 for unrelated to 1 for identical.
+pub fn similarity(a: &[MixerChannel; 3], b: &[MixerChannel; 3]) -> f32
+{
+	sound_similarity(&Sound::from_channels(a), &Sound::from_channels(b))
+}
+
+pub fn sound_similarity(a: &Sound, b: &Sound) -> f32
+{
+	let total_a = a.total_volume();
+	let total_b = b.total_volume();
+	if total_a <= 0.0 || total_b <= 0.0
+	{
+		return if total_a == total_b { 1.0 } else { 0.0 };
+	}
+
+	let mut distance = 0.0;
+	for idx in 0..3
+	{
+		let share_a = a.volumes[idx] / total_a;
+		let share_b = b.volumes[idx] / total_b;
+
+		// Difference in how much of each waveform is in the mix.
+		distance += (share_a - share_b).abs() * SIMILARITY_TIMBRE_WEIGHT;
+
+		// Pitch difference where both have the waveform, counting octaves as close.
+		let shared = share_a.min(share_b);
+		if shared > 0.0
+		{
+			let semitones = (12.0 * (a.freqs[idx] / b.freqs[idx]).log2()).abs();
+			let folded = semitones % 12.0;
+			let pitch = folded.min(12.0 - folded) + (semitones / 12.0).floor() * SIMILARITY_OCTAVE_SEMITONES;
+			distance += shared * pitch * SIMILARITY_PITCH_WEIGHT;
+		}
+	}
+
+	distance += (total_a - total_b).abs() / total_a.max(total_b) * SIMILARITY_LOUDNESS_WEIGHT;
+	return 1.0 / (1.0 + distance);
+}
+
+pub fn is_ambiguous(a: &Sound, b: &Sound) -> bool
+{
+	sound_similarity(a, b) > MAX_SIMILARITY
+}
+
+// Check the target differs from every distractor, and distractors from each other.
+pub fn is_unambiguous(puzzle: &Puzzle) -> bool
+{
+	for idx in 0..puzzle.distractors.len()
+	{
+		if is_ambiguous(&puzzle.target, &puzzle.distractors[idx])
+		{
+			return false;
+		}
+		for other_idx in 0..idx
+		{
+			if is_ambiguous(&puzzle.distractors[other_idx], &puzzle.distractors[idx])
+			{
+				return false;
+			}
+		}
+	}
+	return true;
+}
+
+// Difficulty rises smoothly from 0 at level 1 towards 1.
+pub fn difficulty(level: u32) -> f32
+{
+	let level = if level < 1 { 1 } else { level };
+	1.0 - (-((level - 1) as f32) / DIFFICULTY_SCALE).exp()
+}
+
+// Number of candidates offered at a level.
+pub fn candidate_count(level: u32) -> usize
+{
+	if level < 20
+	{
+		return 2;
+	}
+	if level < 30
+	{
+		return 3;
+	}
+	return (3 + (level as usize - 30) / 5).min(MAX_CANDIDATES);
+}
+
+pub fn target_distance(level: u32) -> f32
+{
+	let d = difficulty(level);
+	EASY_DISTANCE + (HARD_DISTANCE - EASY_DISTANCE) * d
+}
+
+pub fn generate_seeded(seed: u32, level: u32, count: usize, bias: &Bias) -> Puzzle
+{
+	let mut rng: StdRng = SeedableRng::from_seed(&[seed as usize][..]);
+	let mut puzzle = generate(&mut rng, level, count, bias);
+	puzzle.seed = seed;
+	return puzzle;
+}
+
+pub fn generate<R: Rng>(rng: &mut R, level: u32, count: usize, bias: &Bias) -> Puzzle
+{
+	let d = difficulty(level);
+	let lissajous = match bias.family
+	{
+		Some(family) => family == Family::Lissajous,
+		None => level >= LISSAJOUS_MIN_LEVEL && rng.gen::<f32>() < LISSAJOUS_CHANCE,
+	};
+	if lissajous
+	{
+		return generate_lissajous(rng, d, count);
+	}
+
+	let harmony = match bias.family
+	{
+		Some(family) => family == Family::Harmony,
+		None => level >= HARMONY_MIN_LEVEL && rng.gen::<f32>() < HARMONY_CHANCE,
+	};
+	if harmony
+	{
+		return generate_harmony(rng, d, count, &bias.tuning);
+	}
+
+	let mut distance = target_distance(level);
+	let mut target = generate_target(rng, d, bias);
+	let mut distractors = Vec::with_capacity(count);
+	let mut distinguishability = distance;
+
+	for attempt in 0..MAX_ROUND_REROLLS
+	{
+		if attempt > 0
+		{
+			// Couldn't fill the round, so reroll it a little easier.
+			distance = distance * 1.25;
+			distinguishability = distance;
+			target = generate_target(rng, d, bias);
+		}
+
+		distractors.clear();
+		while distractors.len() + 1 < count
+		{
+			match generate_distractor(rng, &target, &distractors, distance, bias)
+			{
+				Some((sound, achieved)) =>
+				{
+					distinguishability = distinguishability.min(achieved);
+					distractors.push(sound);
+				},
+				None => break,
+			}
+		}
+
+		if distractors.len() + 1 >= count
+		{
+			break;
+		}
+	}
+
+	// A round that still can't be filled is offered with fewer candidates,
+	// rather than with ambiguous ones.
+	Puzzle
+	{
+		family: Family::Timbre,
+		phase: 0.0,
+		target: target,
+		distractors: distractors,
+		distinguishability: distinguishability,
+		seed: 0,
+	}
+}
+
+// Every candidate is a unique pair of waveforms on a unique frequency ratio.
+fn generate_lissajous<R: Rng>(rng: &mut R, d: f32, count: usize) -> Puzzle
+{
+	// Ratios get more complex with difficulty.
+	let num_ratios = 2 + (d * (LISSAJOUS_RATIOS.len() - 2) as f32).round() as usize;
+	let base = LISSAJOUS_BASE_FREQ * rng.gen_range(2, 5) as f32;
+
+	let mut options = Vec::new();
+	for x_wave in 0..3
+	{
+		for y_wave in (x_wave + 1)..3
+		{
+			for idx in 0..num_ratios
+			{
+				let (a, b) = LISSAJOUS_RATIOS[idx];
+				options.push((x_wave, y_wave, a, b));
+				if a != b
+				{
+					options.push((x_wave, y_wave, b, a));
+				}
+			}
+		}
+	}
+	rng.shuffle(&mut options);
+
+	// Harder rounds keep distractors close to the target's waveforms and ratio.
+	let target = options.swap_remove(0);
+	let mut scored: Vec<(f32, (usize, usize, f32, f32))> = options.iter().map(|&option|
+	{
+		let mut score = (option.2 / option.3 - target.2 / target.3).abs();
+		if option.0 != target.0 || option.1 != target.1
+		{
+			score += 1.0;
+		}
+		(score * d + rng.gen::<f32>() * (1.0 - d) * 4.0, option)
+	}).collect();
+	scored.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+	let to_sound = |option: (usize, usize, f32, f32)|
+	{
+		let mut sound = Sound
+		{
+			freqs: [base; 3],
+			volumes: [0.0; 3],
+		};
+		sound.freqs[option.0] = base * option.2;
+		sound.freqs[option.1] = base * option.3;
+		sound.volumes[option.0] = 0.5;
+		sound.volumes[option.1] = 0.5;
+		sound
+	};
+
+	let target_sound = to_sound(target);
+	let mut distractors = Vec::with_capacity(count);
+	for &(_, option) in scored.iter()
+	{
+		let sound = to_sound(option);
+		if distractors.len() + 1 < count && !is_ambiguous(&target_sound, &sound) && !distractors.iter().any(|other| is_ambiguous(other, &sound))
+		{
+			distractors.push(sound);
+		}
+	}
+
+	// Always hand back a full round, even if it can't be made unambiguous.
+	for &(_, option) in scored.iter()
+	{
+		if distractors.len() + 1 >= count
+		{
+			break;
+		}
+		distractors.push(to_sound(option));
+	}
+	let distinguishability = distractors.iter().fold(EASY_DISTANCE, |min, distractor|
+	{
+		min.min(1.0 / sound_similarity(&target_sound, distractor) - 1.0)
+	});
+
+	Puzzle
+	{
+		family: Family::Lissajous,
+		phase: rng.gen::<f32>() * 0.5,
+		target: target_sound,
+		distractors: distractors,
+		distinguishability: distinguishability,
+		seed: 0,
+	}
+}
+
+// Every candidate is a different interval or chord on the same root.
+fn generate_harmony<R: Rng>(rng: &mut R, d: f32, count: usize, tuning: &Tuning) -> Puzzle
+{
+	let mut candidates = Vec::with_capacity(count);
+	for _ in 0..MAX_ROUND_REROLLS
+	{
+		let root = rng.gen_range(HARMONY_MIN_ROOT, HARMONY_MAX_ROOT + 1);
+		// Tune from the root so just and Pythagorean intervals are pure.
+		let tuning = Tuning { tonic: root, ..*tuning };
+		let to_sound = |semitones: &[i32]|
+		{
+			let mut sound = Sound
+			{
+				freqs: [tuning.freq(root); 3],
+				volumes: [0.0; 3],
+			};
+			for idx in 0..semitones.len()
+			{
+				sound.freqs[idx] = tuning.freq(root + semitones[idx]);
+				sound.volumes[idx] = HARMONY_VOLUMES[idx];
+			}
+			sound
+		};
+
+		// Harder rounds draw from more, and less familiar, intervals and chords.
+		let num_intervals = (3 + (d * (INTERVALS.len() - 3) as f32).round() as usize).max(count).min(INTERVALS.len());
+		let mut intervals: Vec<Sound> = INTERVALS[..num_intervals].iter().map(|interval| to_sound(&[0, interval.semitones()])).collect();
+		let mut chords: Vec<Sound> = CHORDS.iter().map(|chord| to_sound(&chord.semitones())).collect();
+		rng.shuffle(&mut intervals);
+		rng.shuffle(&mut chords);
+
+		// Chords come first when picked, topped up with intervals if there are too few.
+		let mut options = Vec::with_capacity(intervals.len() + chords.len());
+		if d >= CHORD_MIN_DIFFICULTY && rng.gen()
+		{
+			options.extend(chords.into_iter());
+		}
+		options.extend(intervals.into_iter());
+
+		// Skip any that sound too like one already taken, which close
+		// intervals can in unequal tunings.
+		candidates.clear();
+		for option in options.into_iter()
+		{
+			if candidates.len() == count
+			{
+				break;
+			}
+			if candidates.iter().all(|candidate| sound_similarity(candidate, &option) <= MAX_SIMILARITY)
+			{
+				candidates.push(option);
+			}
+		}
+		if candidates.len() == count
+		{
+			break;
+		}
+	}
+
+	// As with timbre rounds, a round that can't be filled has fewer candidates.
+	let target = candidates[0];
+	let distractors: Vec<Sound> = candidates[1..].to_vec();
+	let distinguishability = distractors.iter().fold(EASY_DISTANCE, |min, distractor|
+	{
+		min.min(1.0 / sound_similarity(&target, distractor) - 1.0)
+	});
+
+	Puzzle
+	{
+		family: Family::Harmony,
+		phase: 0.0,
+		target: target,
+		distractors: distractors,
+		distinguishability: distinguishability,
+		seed: 0,
+	}
+}
+
+// Find a distractor near the wanted distance that isn't ambiguous with any other sound.
+fn generate_distractor<R: Rng>(rng: &mut R, target: &Sound, others: &Vec<Sound>, distance: f32, bias: &Bias) -> Option<(Sound, f32)>
+{
+	let mut best: Option<(Sound, f32)> = None;
+	for _ in 0..MAX_REROLLS
+	{
+		let next = mutate(rng, target, distance, bias);
+		if is_ambiguous(target, &next.0) || others.iter().any(|other| is_ambiguous(other, &next.0))
+		{
+			continue;
+		}
+
+		let achieved = next.1;
+		let is_better = match best
+		{
+			Some((_, best_achieved)) => achieved > best_achieved,
+			None => true,
+		};
+		if is_better
+		{
+			best = Some(next);
+		}
+		if achieved >= distance * 0.75
+		{
+			break;
+		}
+	}
+	return best;
+}
+
+fn generate_target<R: Rng>(rng: &mut R, d: f32, bias: &Bias) -> Sound
+{
+	// Pitch range widens with difficulty, favouring the weaker bands within it.
+	let octaves = 1.0 + 2.0 * d;
+	let mut candidates = [0.0; BAND_CANDIDATES];
+	let mut candidate_weights = [0.0; BAND_CANDIDATES];
+	for idx in 0..BAND_CANDIDATES
+	{
+		candidates[idx] = (pitch::A4_FREQ * 2.0f32.powf(rng.gen_range(-octaves, octaves) * 0.5)).max(MIN_FREQ).min(MAX_FREQ);
+		candidate_weights[idx] = bias.bands[band_of(candidates[idx])];
+	}
+	let base = candidates[choose_weighted(rng, &candidate_weights)];
+
+	// Mix complexity grows with difficulty.
+	let max_active = 1 + (d * 2.99) as usize;
+	let num_active = 1 + choose_weighted(rng, &bias.complexity[..max_active]);
+
+	// Dominant waveform comes first.
+	let dominant = choose_weighted(rng, &bias.waveforms);
+	let mut waves = [dominant, (dominant + 1) % 3, (dominant + 2) % 3];
+	rng.shuffle(&mut waves[1..]);
+
+	let mut sound = Sound
+	{
+		freqs: [base; 3],
+		volumes: [0.0; 3],
+	};
+
+	let total_volume = 0.5 + 0.25 * (num_active - 1) as f32;
+	let mut weights = [0.0; 3];
+	let mut total_weight = 0.0;
+	for idx in 0..num_active
+	{
+		weights[idx] = rng.gen_range(0.5, 1.0);
+		total_weight += weights[idx];
+	}
+	weights[..num_active].sort_by(|a, b| b.partial_cmp(a).unwrap());
+
+	for idx in 0..num_active
+	{
+		let wave = waves[idx];
+		sound.volumes[wave] = total_volume * weights[idx] / total_weight;
+
+		// Upper voices may sit on a harmonic ratio of the root.
+		if idx > 0 && rng.gen::<f32>() < d
+		{
+			sound.freqs[wave] = base * HARMONIC_RATIOS[rng.gen_range(1, HARMONIC_RATIOS.len())];
+		}
+	}
+
+	return sound;
+}
+
+// Mutate a sound by roughly the given distance, returning it with the distance achieved.
+fn mutate<R: Rng>(rng: &mut R, target: &Sound, distance: f32, bias: &Bias) -> (Sound, f32)
+{
+	let mut sound = *target;
+	let mut remaining = distance;
+	for _ in 0..MAX_MUTATIONS
+	{
+		if remaining < 0.01
+		{
+			break;
+		}
+
+		remaining -= match rng.gen_range(0, 4)
+		{
+			0 => shift_timbre(rng, &mut sound, remaining, bias),
+			1 => detune(rng, &mut sound, remaining),
+			2 => change_ratio(rng, &mut sound, remaining),
+			_ => change_volume(rng, &mut sound, remaining),
+		};
+	}
+	return (sound, distance - remaining);
+}
+
+// Move volume from one waveform to another, favouring common confusions.
+fn shift_timbre<R: Rng>(rng: &mut R, sound: &mut Sound, remaining: f32, bias: &Bias) -> f32
+{
+	let active = sound.active();
+	let from = active[rng.gen_range(0, active.len())];
+	let mut to_weights = bias.confusions[from];
+	to_weights[from] = 0.0;
+	let to = choose_weighted(rng, &to_weights);
+	if to == from
+	{
+		return 0.0;
+	}
+
+	let amount = sound.volumes[from].min(remaining / TIMBRE_WEIGHT);
+	if sound.volumes[to] == 0.0
+	{
+		sound.freqs[to] = sound.freqs[from];
+	}
+	sound.volumes[from] -= amount;
+	sound.volumes[to] += amount;
+	return amount * TIMBRE_WEIGHT;
+}
+
+// Detune a single waveform up or down.
+fn detune<R: Rng>(rng: &mut R, sound: &mut Sound, remaining: f32) -> f32
+{
+	let active = sound.active();
+	let wave = active[rng.gen_range(0, active.len())];
+
+	let mut semitones = (remaining / PITCH_WEIGHT).min(12.0);
+	let freq = sound.freqs[wave];
+	if freq * 2.0f32.powf(semitones / 12.0) > MAX_FREQ || (rng.gen() && freq * 2.0f32.powf(-semitones / 12.0) >= MIN_FREQ)
+	{
+		semitones = -semitones;
+	}
+
+	sound.freqs[wave] = freq * 2.0f32.powf(semitones / 12.0);
+	return semitones.abs() * PITCH_WEIGHT;
+}
+
+// Move an upper voice onto a different harmonic ratio of the root.
+fn change_ratio<R: Rng>(rng: &mut R, sound: &mut Sound, remaining: f32) -> f32
+{
+	let active = sound.active();
+	if active.len() < 2 || remaining < RATIO_WEIGHT
+	{
+		return 0.0;
+	}
+
+	let root = sound.root_freq();
+	let wave = active[rng.gen_range(1, active.len())];
+	let ratio = HARMONIC_RATIOS[rng.gen_range(0, HARMONIC_RATIOS.len())];
+	if (sound.freqs[wave] - root * ratio).abs() < 1.0
+	{
+		return 0.0;
+	}
+
+	sound.freqs[wave] = root * ratio;
+	return RATIO_WEIGHT;
+}
+
+// Make a single waveform louder or quieter.
+fn change_volume<R: Rng>(rng: &mut R, sound: &mut Sound, remaining: f32) -> f32
+{
+	let active = sound.active();
+	let wave = active[rng.gen_range(0, active.len())];
+
+	let volume = sound.volumes[wave];
+	let amount = (remaining / VOLUME_WEIGHT).min(volume * 0.75);
+	sound.volumes[wave] = if rng.gen() && volume + amount <= 1.0 { volume + amount } else { volume - amount };
+	return amount * VOLUME_WEIGHT;
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use pitch::{Temperament, A4_NOTE};
+
+	const LEVELS: [u32; 8] = [1, 10, 20, 30, 35, 40, 50, 80];
+	const FAMILIES: [Option<Family>; 4] = [None, Some(Family::Timbre), Some(Family::Lissajous), Some(Family::Harmony)];
+
+	fn candidates(puzzle: &Puzzle) -> Vec<Sound>
+	{
+		let mut sounds = vec![puzzle.target];
+		sounds.extend(puzzle.distractors.iter().cloned());
+		return sounds;
+	}
+
+	#[test]
+	fn no_candidates_are_ambiguous()
+	{
+		let mut bias = Bias::uniform();
+		for &family in FAMILIES.iter()
+		{
+			bias.family = family;
+			for seed in 0..1000
+			{
+				for &level in LEVELS.iter()
+				{
+					let count = candidate_count(level);
+					let puzzle = generate_seeded(seed, level, count, &bias);
+					let sounds = candidates(&puzzle);
+					assert!(sounds.len() >= 2 && sounds.len() <= count, "seed {} level {} family {:?}: {} candidates", seed, level, family, sounds.len());
+					for a in 0..sounds.len()
+					{
+						for b in 0..a
+						{
+							let similarity = sound_similarity(&sounds[a], &sounds[b]);
+							assert!(similarity <= MAX_SIMILARITY, "seed {} level {} family {:?}: candidates {} and {} have similarity {}", seed, level, family, b, a, similarity);
+						}
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn seeded_generation_is_deterministic()
+	{
+		let bias = Bias::uniform();
+		for seed in 0..1000
+		{
+			let level = LEVELS[seed as usize % LEVELS.len()];
+			let a = generate_seeded(seed, level, 8, &bias);
+			let b = generate_seeded(seed, level, 8, &bias);
+			assert_eq!(a.seed, seed);
+			assert_eq!(a.distractors.len(), b.distractors.len());
+			for (sound_a, sound_b) in candidates(&a).iter().zip(candidates(&b).iter())
+			{
+				assert_eq!(sound_a.freqs, sound_b.freqs);
+				assert_eq!(sound_a.volumes, sound_b.volumes);
+			}
+		}
+	}
+
+	#[test]
+	fn harmony_rounds_share_a_root()
+	{
+		let mut bias = Bias::uniform();
+		bias.family = Some(Family::Harmony);
+		bias.tuning = Tuning::with_temperament(Temperament::Just, A4_NOTE);
+		for seed in 0..200
+		{
+			for &level in LEVELS.iter()
+			{
+				let count = candidate_count(level);
+				let puzzle = generate_seeded(seed, level, count, &bias);
+				assert_eq!(puzzle.family, Family::Harmony);
+
+				let sounds = candidates(&puzzle);
+				let root = sounds[0].freqs[0];
+				assert!(root >= pitch::midi_to_freq(HARMONY_MIN_ROOT as f32) * 0.999);
+				assert!(root <= pitch::midi_to_freq(HARMONY_MAX_ROOT as f32) * 1.001);
+				for (idx, sound) in sounds.iter().enumerate()
+				{
+					assert_eq!(sound.freqs[0], root);
+					assert_eq!(sound.volumes[0], HARMONY_VOLUMES[0]);
+					assert!(sound.volumes[1] > 0.0);
+					// Chords only once the difficulty allows them.
+					if difficulty(level) < CHORD_MIN_DIFFICULTY
+					{
+						assert_eq!(sound.volumes[2], 0.0);
+					}
+					// Just intervals above the root are small whole number ratios.
+					let ratio = sound.freqs[1] / root;
+					assert!(ratio > 1.0 && ratio <= 2.001, "seed {} level {}: ratio {}", seed, level, ratio);
+					let cents = pitch::cents_between(root, sound.freqs[1]);
+					assert!((cents / 100.0 - (cents / 100.0).round()).abs() < 0.25);
+
+					for other in sounds[..idx].iter()
+					{
+						assert!(other.freqs != sound.freqs || other.volumes != sound.volumes, "seed {} level {}: repeated candidate", seed, level);
+					}
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,35 @@
+/////////////////////////////////////////////////////////////////////
+// XorShift
+// Small, fast generator for noise and variation on hot paths, such as
+// the audio thread, where a rand::Rng is more than is needed.
+#[derive(Copy, Clone, Debug)]
+pub struct XorShift
+{
+	state: u32,
+}
+
+impl XorShift
+{
+	// Seed must be non-zero, or the generator only returns zero.
+	pub fn new(seed: u32) -> XorShift
+	{
+		XorShift
+		{
+			state: if seed == 0 { 0x2545f491 } else { seed },
+		}
+	}
+
+	pub fn next_u32(&mut self) -> u32
+	{
+		self.state ^= self.state << 13;
+		self.state ^= self.state >> 17;
+		self.state ^= self.state << 5;
+		self.state
+	}
+
+	// Uniform from 0 to 1.
+	pub fn next_f32(&mut self) -> f32
+	{
+		self.next_u32() as f32 / u32::max_value() as f32
+	}
+}
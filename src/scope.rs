@@ -0,0 +1,157 @@
+use std::f32::consts::PI;
+
+pub const SCOPE_BUFFER_SIZE: usize = 4096;
+pub const FFT_SIZE: usize = 1024;
+
+/////////////////////////////////////////////////////////////////////
+// ScopeBuffer
+pub struct ScopeBuffer
+{
+	// Most recent mixer output per side, oldest overwritten first.
+	left: Vec<f32>,
+	right: Vec<f32>,
+	// Next sample to write.
+	write_idx: usize,
+	// Sample rate of the mixer output.
+	pub sample_rate: f32,
+}
+
+impl ScopeBuffer
+{
+	pub fn new(size: usize, in_sample_rate: f32) -> ScopeBuffer
+	{
+		ScopeBuffer
+		{
+			left: vec![0.0; size],
+			right: vec![0.0; size],
+			write_idx: 0,
+			sample_rate: in_sample_rate,
+		}
+	}
+
+	// Write interleaved samples with the given number of channels.
+	pub fn write(&mut self, samples: &[f32], num_channels: usize)
+	{
+		let size = self.left.len();
+		for frame in samples.chunks(num_channels.max(1))
+		{
+			self.left[self.write_idx] = frame[0];
+			self.right[self.write_idx] = if frame.len() > 1 { frame[1] } else { frame[0] };
+			self.write_idx = (self.write_idx + 1) % size;
+		}
+	}
+
+	// Copy the latest count samples mixed to mono into out, oldest first.
+	pub fn read_latest(&self, out: &mut Vec<f32>, count: usize)
+	{
+		let size = self.left.len();
+		let count = count.min(size);
+		out.clear();
+		for idx in 0..count
+		{
+			let read_idx = (self.write_idx + size - count + idx) % size;
+			out.push((self.left[read_idx] + self.right[read_idx]) * 0.5);
+		}
+	}
+
+	// Copy the latest count samples of each side, oldest first.
+	pub fn read_latest_stereo(&self, out_left: &mut Vec<f32>, out_right: &mut Vec<f32>, count: usize)
+	{
+		let size = self.left.len();
+		let count = count.min(size);
+		out_left.clear();
+		out_right.clear();
+		for idx in 0..count
+		{
+			let read_idx = (self.write_idx + size - count + idx) % size;
+			out_left.push(self.left[read_idx]);
+			out_right.push(self.right[read_idx]);
+		}
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// Analysis
+// Index of the first rising zero crossing, so traces hold still between frames.
+pub fn find_trigger(samples: &[f32], search: usize) -> usize
+{
+	let search = search.min(samples.len());
+	for idx in 1..search
+	{
+		if samples[idx - 1] < 0.0 && samples[idx] >= 0.0
+		{
+			return idx;
+		}
+	}
+	return 0;
+}
+
+// Magnitude spectrum of Hann windowed samples. Length must be a power of two.
+pub fn spectrum(samples: &[f32], out: &mut Vec<f32>)
+{
+	let n = samples.len();
+	let mut re = Vec::with_capacity(n);
+	let mut im = vec![0.0; n];
+	for idx in 0..n
+	{
+		let window = 0.5 - 0.5 * (2.0 * PI * idx as f32 / (n - 1) as f32).cos();
+		re.push(samples[idx] * window);
+	}
+
+	fft(&mut re, &mut im);
+
+	out.clear();
+	for idx in 0..(n / 2)
+	{
+		out.push((re[idx] * re[idx] + im[idx] * im[idx]).sqrt() * 4.0 / n as f32);
+	}
+}
+
+// In-place iterative radix-2 FFT.
+fn fft(re: &mut [f32], im: &mut [f32])
+{
+	let n = re.len();
+
+	// Bit reversal permutation.
+	let mut j = 0;
+	for i in 1..n
+	{
+		let mut bit = n >> 1;
+		while j & bit != 0
+		{
+			j ^= bit;
+			bit >>= 1;
+		}
+		j |= bit;
+		if i < j
+		{
+			re.swap(i, j);
+			im.swap(i, j);
+		}
+	}
+
+	// Butterflies.
+	let mut len = 2;
+	while len <= n
+	{
+		let angle = -2.0 * PI / len as f32;
+		let mut start = 0;
+		while start < n
+		{
+			for k in 0..(len / 2)
+			{
+				let (sin, cos) = (angle * k as f32).sin_cos();
+				let a = start + k;
+				let b = a + len / 2;
+				let t_re = re[b] * cos - im[b] * sin;
+				let t_im = re[b] * sin + im[b] * cos;
+				re[b] = re[a] - t_re;
+				im[b] = im[a] - t_im;
+				re[a] += t_re;
+				im[a] += t_im;
+			}
+			start += len;
+		}
+		len <<= 1;
+	}
+}
@@ -0,0 +1,141 @@
+use mixer::{sine_wave, square_wave, sawtooth_wave};
+use random::XorShift;
+
+// Voices that can sound at once. Triggering more steals the oldest.
+pub const MAX_VOICES: usize = 8;
+
+/////////////////////////////////////////////////////////////////////
+// Sfx
+// Named sounds in the bank.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Sfx
+{
+	// Correct pick.
+	Hit,
+	// Wrong pick.
+	Miss,
+	// Every few correct picks in a row.
+	Streak,
+	// More shapes to choose from.
+	LevelUp,
+	MenuMove,
+	MenuSelect,
+	// One second of a countdown.
+	CountdownTick,
+	// An achievement unlocked.
+	Achievement,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Waveform
+{
+	Sine,
+	Square,
+	Sawtooth,
+	Noise,
+}
+
+/////////////////////////////////////////////////////////////////////
+// Patch
+// A short synthesized sound: notes played one after another, each with
+// the same waveform, envelope and pitch sweep.
+#[derive(Copy, Clone, Debug)]
+pub struct Patch
+{
+	pub wave: Waveform,
+	// Frequency of each note in Hz.
+	pub notes: &'static [f32],
+	// Seconds from one note to the next, and of the last note.
+	pub note_length: f32,
+	// Seconds to reach full volume.
+	pub attack: f32,
+	// Seconds to fall to about a third of full volume.
+	pub decay: f32,
+	// Pitch change in octaves per second over each note.
+	pub sweep: f32,
+	pub volume: f32,
+}
+
+impl Sfx
+{
+	pub fn patch(&self) -> Patch
+	{
+		match *self
+		{
+			Sfx::Hit => Patch { wave: Waveform::Sine, notes: &[1318.5, 1670.0], note_length: 0.08, attack: 0.002, decay: 0.08, sweep: 0.0, volume: 1.0 },
+			Sfx::Miss => Patch { wave: Waveform::Sawtooth, notes: &[110.0], note_length: 0.4, attack: 0.005, decay: 0.2, sweep: -1.5, volume: 0.8 },
+			Sfx::Streak => Patch { wave: Waveform::Square, notes: &[880.0, 1108.7, 1318.5, 1760.0], note_length: 0.06, attack: 0.002, decay: 0.05, sweep: 0.0, volume: 0.4 },
+			Sfx::LevelUp => Patch { wave: Waveform::Sine, notes: &[523.25, 659.25, 783.99, 1046.5, 1318.5], note_length: 0.09, attack: 0.005, decay: 0.12, sweep: 0.2, volume: 0.9 },
+			Sfx::MenuMove => Patch { wave: Waveform::Square, notes: &[1200.0], note_length: 0.03, attack: 0.001, decay: 0.015, sweep: 0.0, volume: 0.25 },
+			Sfx::MenuSelect => Patch { wave: Waveform::Square, notes: &[800.0, 1200.0], note_length: 0.05, attack: 0.001, decay: 0.03, sweep: 0.0, volume: 0.3 },
+			Sfx::CountdownTick => Patch { wave: Waveform::Noise, notes: &[4000.0], note_length: 0.03, attack: 0.001, decay: 0.008, sweep: 0.0, volume: 0.6 },
+			Sfx::Achievement => Patch { wave: Waveform::Square, notes: &[659.25, 783.99, 1046.5, 1046.5, 1318.5, 1568.0], note_length: 0.1, attack: 0.004, decay: 0.1, sweep: 0.0, volume: 0.45 },
+		}
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// Voice
+// One playing patch.
+#[derive(Copy, Clone)]
+pub struct Voice
+{
+	patch: Patch,
+	// Seconds since triggered.
+	pub time: f32,
+	phase: f32,
+	// State for the noise waveform.
+	noise: XorShift,
+	noise_value: f32,
+}
+
+impl Voice
+{
+	pub fn new(sfx: Sfx) -> Voice
+	{
+		Voice
+		{
+			patch: sfx.patch(),
+			time: 0.0,
+			phase: 0.0,
+			noise: XorShift::new(0x1234567),
+			noise_value: 0.0,
+		}
+	}
+
+	pub fn is_finished(&self) -> bool
+	{
+		self.time >= self.patch.note_length * self.patch.notes.len() as f32
+	}
+
+	// Next sample at sample rate freq, advancing the voice.
+	pub fn sample(&mut self, freq: f32) -> f32
+	{
+		let note_idx = ((self.time / self.patch.note_length) as usize).min(self.patch.notes.len() - 1);
+		let note_time = self.time - note_idx as f32 * self.patch.note_length;
+		let note_freq = self.patch.notes[note_idx] * (self.patch.sweep * note_time).exp2();
+
+		let old_phase = self.phase;
+		self.phase = (self.phase + note_freq / freq) % 1.0;
+		let wave = match self.patch.wave
+		{
+			Waveform::Sine => sine_wave(self.phase),
+			Waveform::Square => square_wave(self.phase),
+			Waveform::Sawtooth => sawtooth_wave(self.phase),
+			Waveform::Noise =>
+			{
+				// New random value each cycle, so the note sets the noise colour.
+				if self.phase < old_phase
+				{
+					self.noise_value = self.noise.next_f32() * 2.0 - 1.0;
+				}
+				self.noise_value
+			},
+		};
+
+		let attack = (note_time / self.patch.attack).min(1.0);
+		let decay = (-note_time / self.patch.decay).exp();
+		self.time += 1.0 / freq;
+		wave * attack * decay * self.patch.volume
+	}
+}
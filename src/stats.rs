@@ -0,0 +1,278 @@
+use std::io;
+use std::io::prelude::*;
+use std::fs::File;
+use puzzle::{Bias, Sound, NUM_BANDS};
+
+// Lowest weight a category can get, so nothing stops appearing entirely.
+const MIN_WEIGHT: f32 = 0.25;
+
+/////////////////////////////////////////////////////////////////////
+// Accuracy
+#[derive(Copy, Clone, Debug)]
+pub struct Accuracy
+{
+	pub hits: u32,
+	pub attempts: u32,
+}
+
+impl Accuracy
+{
+	pub fn new() -> Accuracy
+	{
+		Accuracy
+		{
+			hits: 0,
+			attempts: 0,
+		}
+	}
+
+	pub fn record(&mut self, hit: bool)
+	{
+		self.attempts += 1;
+		if hit
+		{
+			self.hits += 1;
+		}
+	}
+
+	// Smoothed miss rate, 0.5 before any attempts.
+	pub fn miss_rate(&self) -> f32
+	{
+		(self.attempts - self.hits + 1) as f32 / (self.attempts + 2) as f32
+	}
+
+	fn weight(&self) -> f32
+	{
+		MIN_WEIGHT + self.miss_rate()
+	}
+}
+
+// Whole contents of a save file, or None if it's missing or can't be read,
+// in which case the caller starts afresh.
+pub fn read_save_file(filename: &str) -> Option<String>
+{
+	let mut s = String::new();
+	match File::open(filename)
+	{
+		Ok(mut file) => file.read_to_string(&mut s).ok().map(|_| s),
+		Err(..) => None,
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// PlayerStats
+pub struct PlayerStats
+{
+	// Accuracy by dominant waveform of the target.
+	pub waveforms: [Accuracy; 3],
+	// Accuracy by frequency band of the target.
+	pub bands: [Accuracy; NUM_BANDS],
+	// Accuracy by number of waveforms in the target.
+	pub complexity: [Accuracy; 3],
+	// Times a target with one dominant waveform was mistaken for another.
+	pub confusions: [[u32; 3]; 3],
+}
+
+impl PlayerStats
+{
+	pub fn new() -> PlayerStats
+	{
+		PlayerStats
+		{
+			waveforms: [Accuracy::new(); 3],
+			bands: [Accuracy::new(); NUM_BANDS],
+			complexity: [Accuracy::new(); 3],
+			confusions: [[0; 3]; 3],
+		}
+	}
+
+	// Load stats, starting afresh if the file is missing. Unrecognised lines are skipped.
+	pub fn load(filename: &str) -> PlayerStats
+	{
+		let mut stats = PlayerStats::new();
+		let s = match read_save_file(filename)
+		{
+			Some(s) => s,
+			None => return stats,
+		};
+
+		for line in s.lines()
+		{
+			let parts: Vec<&str> = line.split_whitespace().collect();
+			if parts.len() != 4
+			{
+				continue;
+			}
+			let values: Vec<u32> = parts[1..].iter().filter_map(|part| part.parse::<u32>().ok()).collect();
+			if values.len() != 3
+			{
+				continue;
+			}
+
+			let idx = values[0] as usize;
+			let accuracy = Accuracy { hits: values[1].min(values[2]), attempts: values[2] };
+			match parts[0]
+			{
+				"waveform" if idx < 3 => stats.waveforms[idx] = accuracy,
+				"band" if idx < NUM_BANDS => stats.bands[idx] = accuracy,
+				"complexity" if idx < 3 => stats.complexity[idx] = accuracy,
+				"confusion" if idx < 3 && values[1] < 3 => stats.confusions[idx][values[1] as usize] = values[2],
+				_ => {},
+			}
+		}
+		return stats;
+	}
+
+	pub fn save(&self, filename: &str) -> io::Result<()>
+	{
+		let mut file = try!(File::create(filename));
+		for idx in 0..3
+		{
+			try!(writeln!(file, "waveform {} {} {}", idx, self.waveforms[idx].hits, self.waveforms[idx].attempts));
+		}
+		for idx in 0..NUM_BANDS
+		{
+			try!(writeln!(file, "band {} {} {}", idx, self.bands[idx].hits, self.bands[idx].attempts));
+		}
+		for idx in 0..3
+		{
+			try!(writeln!(file, "complexity {} {} {}", idx, self.complexity[idx].hits, self.complexity[idx].attempts));
+		}
+		for from in 0..3
+		{
+			for to in 0..3
+			{
+				try!(writeln!(file, "confusion {} {} {}", from, to, self.confusions[from][to]));
+			}
+		}
+		Ok(())
+	}
+
+	// Record an answer, where picked is the sound the player chose.
+	pub fn record(&mut self, target: &Sound, picked: &Sound, hit: bool)
+	{
+		let wave = target.dominant_wave();
+		self.waveforms[wave].record(hit);
+		self.bands[target.band()].record(hit);
+		self.complexity[target.complexity().max(1) - 1].record(hit);
+
+		if !hit
+		{
+			self.confusions[wave][picked.dominant_wave()] += 1;
+		}
+	}
+
+	pub fn bias(&self) -> Bias
+	{
+		let mut bias = Bias::uniform();
+		for idx in 0..3
+		{
+			bias.waveforms[idx] = self.waveforms[idx].weight();
+			bias.complexity[idx] = self.complexity[idx].weight();
+			for to in 0..3
+			{
+				bias.confusions[idx][to] = 1.0 + self.confusions[idx][to] as f32;
+			}
+		}
+		for idx in 0..NUM_BANDS
+		{
+			bias.bands[idx] = self.bands[idx].weight();
+		}
+		return bias;
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::env;
+	use std::fs;
+	use std::process;
+	use super::*;
+
+	fn sound(wave: usize, freq: f32) -> Sound
+	{
+		let mut sound = Sound { freqs: [freq; 3], volumes: [0.0; 3] };
+		sound.volumes[wave] = 0.5;
+		return sound;
+	}
+
+	#[test]
+	fn miss_rate_is_smoothed()
+	{
+		let mut accuracy = Accuracy::new();
+		assert_eq!(accuracy.miss_rate(), 0.5);
+		accuracy.record(true);
+		accuracy.record(true);
+		assert_eq!(accuracy.miss_rate(), 0.25);
+		accuracy.record(false);
+		accuracy.record(false);
+		assert_eq!(accuracy.miss_rate(), 0.5);
+	}
+
+	#[test]
+	fn bias_favours_misses()
+	{
+		let mut stats = PlayerStats::new();
+		for _ in 0..8
+		{
+			stats.record(&sound(0, 300.0), &sound(0, 300.0), true);
+			stats.record(&sound(2, 100.0), &sound(1, 100.0), false);
+		}
+		let bias = stats.bias();
+		assert!(bias.waveforms[2] > bias.waveforms[1] && bias.waveforms[1] > bias.waveforms[0]);
+		assert!(bias.bands[0] > bias.bands[2] && bias.bands[2] > bias.bands[1]);
+		assert_eq!(bias.waveforms[0], MIN_WEIGHT + 0.1);
+		assert_eq!(bias.complexity[0], MIN_WEIGHT + 0.5);
+		assert_eq!(bias.complexity[1], MIN_WEIGHT + 0.5);
+		assert_eq!(bias.confusions[2][1], 9.0);
+		assert_eq!(bias.confusions[2][0], 1.0);
+		assert_eq!(bias.confusions[0][0], 1.0);
+	}
+
+	#[test]
+	fn save_and_load_round_trip()
+	{
+		let path = env::temp_dir().join(format!("oscillomatch-stats-{}.txt", process::id()));
+		let filename = path.to_str().unwrap();
+
+		let mut stats = PlayerStats::new();
+		stats.record(&sound(1, 500.0), &sound(0, 500.0), false);
+		stats.record(&sound(1, 500.0), &sound(1, 500.0), true);
+		stats.record(&sound(0, 150.0), &sound(0, 150.0), true);
+		stats.save(filename).unwrap();
+
+		let loaded = PlayerStats::load(filename);
+		fs::remove_file(filename).unwrap();
+		for idx in 0..3
+		{
+			assert_eq!((loaded.waveforms[idx].hits, loaded.waveforms[idx].attempts), (stats.waveforms[idx].hits, stats.waveforms[idx].attempts));
+			assert_eq!((loaded.complexity[idx].hits, loaded.complexity[idx].attempts), (stats.complexity[idx].hits, stats.complexity[idx].attempts));
+		}
+		for idx in 0..NUM_BANDS
+		{
+			assert_eq!((loaded.bands[idx].hits, loaded.bands[idx].attempts), (stats.bands[idx].hits, stats.bands[idx].attempts));
+		}
+		assert_eq!(loaded.confusions, stats.confusions);
+		assert_eq!(loaded.confusions[1][0], 1);
+
+		// Missing files start afresh.
+		assert_eq!(PlayerStats::load(filename).waveforms[1].attempts, 0);
+	}
+
+	#[test]
+	fn load_skips_bad_lines()
+	{
+		let path = env::temp_dir().join(format!("oscillomatch-stats-bad-{}.txt", process::id()));
+		let filename = path.to_str().unwrap();
+		fs::write(filename, "waveform 1 3 4\nwaveform 7 1 1\nband 0 x 2\nconfusion 0 5 2\nbogus\ncomplexity 2 9 4\n").unwrap();
+
+		let stats = PlayerStats::load(filename);
+		fs::remove_file(filename).unwrap();
+		assert_eq!((stats.waveforms[1].hits, stats.waveforms[1].attempts), (3, 4));
+		assert_eq!(stats.bands[0].attempts, 0);
+		assert_eq!(stats.confusions, [[0; 3]; 3]);
+		// Hits are capped at attempts.
+		assert_eq!((stats.complexity[2].hits, stats.complexity[2].attempts), (4, 4));
+	}
+}
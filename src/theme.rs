@@ -0,0 +1,255 @@
+use sdl2::pixels::Color;
+
+pub const THEME_NAMES: [&'static str; 5] = ["green", "amber", "white", "high-contrast", "deuteranopia"];
+
+/////////////////////////////////////////////////////////////////////
+// Theme
+// Every colour drawn on screen. Hit and miss colours must differ by more
+// than hue alone so they still read in colour-blind palettes.
+#[derive(Copy, Clone, Debug)]
+pub struct Theme
+{
+	pub name: &'static str,
+	pub background: Color,
+	// Shape outlines, idle and under the mouse.
+	pub shape: Color,
+	pub shape_hover: Color,
+	// Shape outline flashing after a correct pick, and after a wrong one.
+	pub shape_correct: Color,
+	pub shape_wrong: Color,
+	// Popups and particles for correct picks.
+	pub hit: Color,
+	pub hit_spark: Color,
+	// Popups and particles for wrong picks.
+	pub miss: Color,
+	pub miss_spark: Color,
+	pub score: Color,
+	pub high_score: Color,
+	// Screen noise and ambient motes.
+	pub noise: Color,
+	// Hint trace and debug scope overlay.
+	pub scope: Color,
+	pub scope_debug: Color,
+}
+
+impl Theme
+{
+	// Classic P1 green phosphor.
+	pub fn green() -> Theme
+	{
+		Theme
+		{
+			name: "green",
+			background: Color::RGB(0, 0, 0),
+			shape: Color::RGB(0, 128, 0),
+			shape_hover: Color::RGB(0, 255, 0),
+			shape_correct: Color::RGB(192, 255, 192),
+			shape_wrong: Color::RGB(255, 0, 0),
+			hit: Color::RGB(0, 255, 0),
+			hit_spark: Color::RGB(192, 255, 192),
+			miss: Color::RGB(255, 0, 0),
+			miss_spark: Color::RGB(255, 192, 64),
+			score: Color::RGB(0, 128, 0),
+			high_score: Color::RGB(0, 128, 128),
+			noise: Color::RGB(0, 255, 0),
+			scope: Color::RGB(0, 192, 0),
+			scope_debug: Color::RGB(0, 192, 192),
+		}
+	}
+
+	// P3 amber phosphor.
+	pub fn amber() -> Theme
+	{
+		Theme
+		{
+			name: "amber",
+			background: Color::RGB(0, 0, 0),
+			shape: Color::RGB(160, 100, 0),
+			shape_hover: Color::RGB(255, 176, 0),
+			shape_correct: Color::RGB(255, 230, 160),
+			shape_wrong: Color::RGB(255, 64, 0),
+			hit: Color::RGB(255, 176, 0),
+			hit_spark: Color::RGB(255, 230, 160),
+			miss: Color::RGB(255, 64, 0),
+			miss_spark: Color::RGB(255, 128, 64),
+			score: Color::RGB(160, 100, 0),
+			high_score: Color::RGB(255, 208, 96),
+			noise: Color::RGB(255, 176, 0),
+			scope: Color::RGB(224, 150, 0),
+			scope_debug: Color::RGB(255, 208, 96),
+		}
+	}
+
+	// P4 white phosphor.
+	pub fn white() -> Theme
+	{
+		Theme
+		{
+			name: "white",
+			background: Color::RGB(0, 0, 0),
+			shape: Color::RGB(150, 150, 160),
+			shape_hover: Color::RGB(230, 230, 240),
+			shape_correct: Color::RGB(255, 255, 255),
+			shape_wrong: Color::RGB(255, 96, 96),
+			hit: Color::RGB(230, 230, 240),
+			hit_spark: Color::RGB(255, 255, 255),
+			miss: Color::RGB(255, 96, 96),
+			miss_spark: Color::RGB(255, 160, 160),
+			score: Color::RGB(150, 150, 160),
+			high_score: Color::RGB(200, 200, 220),
+			noise: Color::RGB(200, 200, 220),
+			scope: Color::RGB(200, 200, 210),
+			scope_debug: Color::RGB(160, 200, 255),
+		}
+	}
+
+	// Saturated primaries at full brightness on black.
+	pub fn high_contrast() -> Theme
+	{
+		Theme
+		{
+			name: "high-contrast",
+			background: Color::RGB(0, 0, 0),
+			shape: Color::RGB(255, 255, 255),
+			shape_hover: Color::RGB(255, 255, 0),
+			shape_correct: Color::RGB(0, 255, 255),
+			shape_wrong: Color::RGB(255, 0, 255),
+			hit: Color::RGB(0, 255, 255),
+			hit_spark: Color::RGB(255, 255, 255),
+			miss: Color::RGB(255, 0, 255),
+			miss_spark: Color::RGB(255, 255, 0),
+			score: Color::RGB(255, 255, 255),
+			high_score: Color::RGB(255, 255, 0),
+			noise: Color::RGB(255, 255, 255),
+			scope: Color::RGB(255, 255, 255),
+			scope_debug: Color::RGB(0, 255, 255),
+		}
+	}
+
+	// Blue for hits and orange for misses, from the Okabe-Ito palette.
+	pub fn deuteranopia() -> Theme
+	{
+		Theme
+		{
+			name: "deuteranopia",
+			background: Color::RGB(0, 0, 0),
+			shape: Color::RGB(0, 114, 178),
+			shape_hover: Color::RGB(86, 180, 233),
+			shape_correct: Color::RGB(204, 236, 255),
+			shape_wrong: Color::RGB(230, 159, 0),
+			hit: Color::RGB(86, 180, 233),
+			hit_spark: Color::RGB(204, 236, 255),
+			miss: Color::RGB(230, 159, 0),
+			miss_spark: Color::RGB(240, 228, 66),
+			score: Color::RGB(0, 114, 178),
+			high_score: Color::RGB(86, 180, 233),
+			noise: Color::RGB(86, 180, 233),
+			scope: Color::RGB(86, 180, 233),
+			scope_debug: Color::RGB(204, 121, 167),
+		}
+	}
+
+	pub fn by_name(name: &str) -> Option<Theme>
+	{
+		match name
+		{
+			"green" => Some(Theme::green()),
+			"amber" => Some(Theme::amber()),
+			"white" => Some(Theme::white()),
+			"high-contrast" => Some(Theme::high_contrast()),
+			"deuteranopia" => Some(Theme::deuteranopia()),
+			_ => None,
+		}
+	}
+
+	// Theme after this one in THEME_NAMES, wrapping around.
+	pub fn next(&self) -> Theme
+	{
+		let idx = THEME_NAMES.iter().position(|&name| name == self.name).unwrap_or(0);
+		Theme::by_name(THEME_NAMES[(idx + 1) % THEME_NAMES.len()]).unwrap()
+	}
+
+	// Previous theme in THEME_NAMES, wrapping around.
+	pub fn previous(&self) -> Theme
+	{
+		let idx = THEME_NAMES.iter().position(|&name| name == self.name).unwrap_or(0);
+		Theme::by_name(THEME_NAMES[(idx + THEME_NAMES.len() - 1) % THEME_NAMES.len()]).unwrap()
+	}
+
+	// Every colour with its config key.
+	pub fn colors(&self) -> [(&'static str, Color); 14]
+	{
+		[
+			("background", self.background),
+			("shape", self.shape),
+			("shape_hover", self.shape_hover),
+			("shape_correct", self.shape_correct),
+			("shape_wrong", self.shape_wrong),
+			("hit", self.hit),
+			("hit_spark", self.hit_spark),
+			("miss", self.miss),
+			("miss_spark", self.miss_spark),
+			("score", self.score),
+			("high_score", self.high_score),
+			("noise", self.noise),
+			("scope", self.scope),
+			("scope_debug", self.scope_debug),
+		]
+	}
+
+	// Override one colour, given as "r g b".
+	pub fn set(&mut self, key: &str, value: &str) -> Result<(), String>
+	{
+		let color = match parse_color(value)
+		{
+			Some(color) => color,
+			None => return Err(format!("{} must be three numbers from 0 to 255, got '{}'", key, value)),
+		};
+		match key
+		{
+			"background" => self.background = color,
+			"shape" => self.shape = color,
+			"shape_hover" => self.shape_hover = color,
+			"shape_correct" => self.shape_correct = color,
+			"shape_wrong" => self.shape_wrong = color,
+			"hit" => self.hit = color,
+			"hit_spark" => self.hit_spark = color,
+			"miss" => self.miss = color,
+			"miss_spark" => self.miss_spark = color,
+			"score" => self.score = color,
+			"high_score" => self.high_score = color,
+			"noise" => self.noise = color,
+			"scope" => self.scope = color,
+			"scope_debug" => self.scope_debug = color,
+			_ => return Err(format!("unknown colour '{}' in [theme]", key)),
+		}
+		return Ok(());
+	}
+}
+
+// Copy of color with a different alpha.
+pub fn with_alpha(color: Color, alpha: u8) -> Color
+{
+	let (r, g, b) = color.rgb();
+	Color::RGBA(r, g, b, alpha)
+}
+
+// Red, green, blue and alpha of color, with plain RGB opaque.
+pub fn color_components(color: Color) -> (u8, u8, u8, u8)
+{
+	match color
+	{
+		Color::RGB(r, g, b) => (r, g, b, 255),
+		Color::RGBA(r, g, b, a) => (r, g, b, a),
+	}
+}
+
+fn parse_color(value: &str) -> Option<Color>
+{
+	let values: Vec<u8> = value.split_whitespace().filter_map(|part| part.parse::<u8>().ok()).collect();
+	if values.len() != 3
+	{
+		return None;
+	}
+	Some(Color::RGB(values[0], values[1], values[2]))
+}
@@ -0,0 +1,108 @@
+use std::f32::consts::PI;
+
+/////////////////////////////////////////////////////////////////////
+// Easing
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Easing
+{
+	Linear,
+	QuadIn,
+	QuadOut,
+	QuadInOut,
+	CubicOut,
+	BackOut,
+	ElasticOut,
+}
+
+// Map linear progress t in [0, 1] through an easing curve.
+pub fn ease(easing: Easing, t: f32) -> f32
+{
+	let t = t.max(0.0).min(1.0);
+	match easing
+	{
+		Easing::Linear => t,
+		Easing::QuadIn => t * t,
+		Easing::QuadOut => t * (2.0 - t),
+		Easing::QuadInOut =>
+		{
+			if t < 0.5 { 2.0 * t * t } else { -1.0 + (4.0 - 2.0 * t) * t }
+		},
+		Easing::CubicOut =>
+		{
+			let f = t - 1.0;
+			f * f * f + 1.0
+		},
+		Easing::BackOut =>
+		{
+			let s = 1.70158;
+			let f = t - 1.0;
+			f * f * ((s + 1.0) * f + s) + 1.0
+		},
+		Easing::ElasticOut =>
+		{
+			if t == 0.0 || t == 1.0
+			{
+				return t;
+			}
+			2.0f32.powf(-10.0 * t) * ((t - 0.075) * (2.0 * PI) / 0.3).sin() + 1.0
+		},
+	}
+}
+
+/////////////////////////////////////////////////////////////////////
+// Tween
+#[derive(Copy, Clone, Debug)]
+pub struct Tween
+{
+	pub from: f32,
+	pub to: f32,
+	pub duration: f32,
+	pub elapsed: f32,
+	pub easing: Easing,
+}
+
+impl Tween
+{
+	pub fn new(in_from: f32, in_to: f32, in_duration: f32, in_easing: Easing) -> Tween
+	{
+		Tween
+		{
+			from: in_from,
+			to: in_to,
+			duration: in_duration,
+			elapsed: 0.0,
+			easing: in_easing,
+		}
+	}
+
+	// Tween that has already reached value.
+	pub fn finished(value: f32) -> Tween
+	{
+		Tween::new(value, value, 0.0, Easing::Linear)
+	}
+
+	pub fn update(&mut self, tick: f32)
+	{
+		self.elapsed = (self.elapsed + tick).min(self.duration);
+	}
+
+	// Linear progress from 0 to 1.
+	pub fn progress(&self) -> f32
+	{
+		if self.duration <= 0.0
+		{
+			return 1.0;
+		}
+		self.elapsed / self.duration
+	}
+
+	pub fn value(&self) -> f32
+	{
+		self.from + (self.to - self.from) * ease(self.easing, self.progress())
+	}
+
+	pub fn is_finished(&self) -> bool
+	{
+		self.elapsed >= self.duration
+	}
+}